@@ -0,0 +1,83 @@
+//! Which staff roles may perform which sensitive operations.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    Cashier,
+    Manager,
+    Admin,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Permission {
+    /// Changing items, prices and sales in the catalog.
+    EditCatalog,
+    /// Replacing the price of a line in a customer's cart.
+    OverridePrice,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PermissionDenied {
+    pub role: Role,
+    pub permission: Permission,
+}
+
+impl std::fmt::Display for PermissionDenied {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "permission denied: {:?} can't perform {:?}",
+            self.role, self.permission
+        )
+    }
+}
+
+impl std::error::Error for PermissionDenied {}
+
+impl Role {
+    pub fn can(self, permission: Permission) -> bool {
+        match permission {
+            Permission::EditCatalog => self == Role::Admin,
+            Permission::OverridePrice => self != Role::Cashier,
+        }
+    }
+
+    /// Call before performing a sensitive operation on behalf of someone in this role.
+    pub fn authorize(self, permission: Permission) -> Result<(), PermissionDenied> {
+        if self.can(permission) {
+            Ok(())
+        } else {
+            Err(PermissionDenied {
+                role: self,
+                permission,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorize() {
+        assert_eq!(
+            Role::Cashier.authorize(Permission::EditCatalog),
+            Err(PermissionDenied {
+                role: Role::Cashier,
+                permission: Permission::EditCatalog,
+            })
+        );
+        assert!(Role::Manager.authorize(Permission::EditCatalog).is_err());
+        assert_eq!(Role::Admin.authorize(Permission::EditCatalog), Ok(()));
+        assert!(Role::Cashier.authorize(Permission::OverridePrice).is_err());
+        assert!(Role::Manager.authorize(Permission::OverridePrice).is_ok());
+        assert!(Role::Admin.authorize(Permission::OverridePrice).is_ok());
+        assert_eq!(
+            Role::Manager
+                .authorize(Permission::EditCatalog)
+                .unwrap_err()
+                .to_string(),
+            "permission denied: Manager can't perform EditCatalog"
+        );
+    }
+}
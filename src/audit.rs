@@ -0,0 +1,105 @@
+//! An append-only record of who changed what, with the value before and after, for the
+//! franchise's audits. Like the rest of the demo's state, it's kept in memory.
+
+use crate::access::Role;
+
+/// Who made a change.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Actor {
+    Customer,
+    Staff(Role),
+    /// A scheduled job, like applying scheduled prices or purging the trash.
+    System,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditEntry {
+    pub actor: Actor,
+    /// The operation, e.g. "set_price".
+    pub action: &'static str,
+    /// What was changed, e.g. "item 3" or "Cookie".
+    pub subject: String,
+    /// `None` when the change created the subject.
+    pub before: Option<serde_json::Value>,
+    /// `None` when the change removed the subject.
+    pub after: Option<serde_json::Value>,
+}
+
+/// A value as recorded in an `AuditEntry`.
+pub fn snapshot(value: &impl serde::Serialize) -> Option<serde_json::Value> {
+    serde_json::to_value(value).ok()
+}
+
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    entries: Vec<AuditEntry>,
+}
+
+impl AuditLog {
+    /// Appends an entry. Entries can't be changed or removed once recorded.
+    pub fn record(
+        &mut self,
+        actor: Actor,
+        action: &'static str,
+        subject: impl Into<String>,
+        before: Option<serde_json::Value>,
+        after: Option<serde_json::Value>,
+    ) {
+        self.entries.push(AuditEntry {
+            actor,
+            action,
+            subject: subject.into(),
+            before,
+            after,
+        });
+    }
+
+    /// Every entry, oldest first.
+    pub fn entries(&self) -> &[AuditEntry] {
+        &self.entries
+    }
+
+    /// The entries about `subject`, oldest first.
+    pub fn about<'l>(&'l self, subject: &'l str) -> impl Iterator<Item = &'l AuditEntry> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.subject == subject)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_log() {
+        let mut log = AuditLog::default();
+        log.record(
+            Actor::Staff(Role::Admin),
+            "set_price",
+            "item 3",
+            snapshot(&1.25),
+            snapshot(&1.5),
+        );
+        log.record(Actor::Customer, "add", "Cookie", None, snapshot(&6));
+        log.record(
+            Actor::System,
+            "purge_trash",
+            "item 3",
+            snapshot(&"Cookie"),
+            None,
+        );
+
+        assert_eq!(log.entries().len(), 3);
+        assert_eq!(
+            log.about("item 3")
+                .map(|entry| entry.actor)
+                .collect::<Vec<_>>(),
+            vec![Actor::Staff(Role::Admin), Actor::System]
+        );
+        assert_eq!(
+            log.about("Cookie").next().unwrap().after,
+            Some(serde_json::json!(6))
+        );
+    }
+}
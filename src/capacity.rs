@@ -0,0 +1,83 @@
+//! How many of each made-to-order item the kitchen can produce per day, independent of stock.
+
+use chrono::NaiveDate;
+use std::collections::HashMap;
+
+#[derive(Default)]
+pub struct ProductionCapacity {
+    daily_limits: HashMap<u32, u32>,
+    booked: HashMap<(u32, NaiveDate), u32>,
+}
+
+impl ProductionCapacity {
+    pub fn set_daily_limit(&mut self, item_id: u32, limit: u32) {
+        self.daily_limits.insert(item_id, limit);
+    }
+
+    /// How many more can be produced on `date`, or `None` if the item isn't capacity-limited.
+    pub fn remaining(&self, item_id: u32, date: NaiveDate) -> Option<u32> {
+        let limit = self.daily_limits.get(&item_id)?;
+        let booked = self.booked.get(&(item_id, date)).copied().unwrap_or(0);
+        Some(limit.saturating_sub(booked))
+    }
+
+    /// The dates in `from..=to` with nothing left to book, for greying out in a date picker.
+    pub fn full_dates(&self, item_id: u32, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        from.iter_days()
+            .take_while(|date| *date <= to)
+            .filter(|date| self.remaining(item_id, *date) == Some(0))
+            .collect()
+    }
+
+    /// Whether every line fits on `date`. Nothing is booked.
+    pub fn check(&self, lines: &[(u32, u32)], date: NaiveDate) -> Result<(), u32> {
+        for (item_id, quantity) in lines {
+            if self
+                .remaining(*item_id, date)
+                .is_some_and(|remaining| *quantity > remaining)
+            {
+                return Err(*item_id);
+            }
+        }
+        Ok(())
+    }
+
+    /// Books every line on `date`, or none of them if one doesn't fit. The error is the item that
+    /// didn't fit.
+    pub fn reserve(&mut self, lines: &[(u32, u32)], date: NaiveDate) -> Result<(), u32> {
+        self.check(lines, date)?;
+        for (item_id, quantity) in lines {
+            if self.daily_limits.contains_key(item_id) {
+                *self.booked.entry((*item_id, date)).or_insert(0) += quantity;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_production_capacity() {
+        let friday = NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let saturday = NaiveDate::from_ymd_opt(2021, 10, 2).unwrap();
+        let mut capacity = ProductionCapacity::default();
+        capacity.set_daily_limit(6, 5);
+
+        assert_eq!(capacity.remaining(6, friday), Some(5));
+        assert_eq!(capacity.remaining(1, friday), None);
+
+        assert_eq!(capacity.reserve(&[(1, 40), (6, 3)], friday), Ok(()));
+        assert_eq!(capacity.remaining(6, friday), Some(2));
+        assert_eq!(capacity.reserve(&[(1, 40), (6, 3)], friday), Err(6));
+        assert_eq!(capacity.remaining(6, friday), Some(2));
+        assert_eq!(capacity.reserve(&[(6, 2)], friday), Ok(()));
+
+        assert_eq!(
+            capacity.full_dates(6, friday - chrono::Days::new(1), saturday),
+            vec![friday]
+        );
+    }
+}
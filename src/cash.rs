@@ -0,0 +1,56 @@
+//! Taking cash at the register, where totals are rounded to the nearest nickel.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CashPayment {
+    /// What the lines actually came to.
+    pub total: f64,
+    /// The total rounded to the nearest nickel, which is what the customer pays.
+    pub due: f64,
+    pub tendered: f64,
+    pub change: f64,
+}
+
+/// Rounds to the nearest $0.05, with halves rounding up.
+pub fn round_to_nickel(amount: f64) -> f64 {
+    let cents = (amount * 100.0).round() as i64;
+    ((cents + 2) / 5 * 5) as f64 / 100.0
+}
+
+impl CashPayment {
+    /// Fails with the amount due if `tendered` doesn't cover it.
+    pub fn new(total: f64, tendered: f64) -> Result<Self, f64> {
+        let due = round_to_nickel(total);
+        if tendered < due {
+            return Err(due);
+        }
+        Ok(Self {
+            total,
+            due,
+            tendered,
+            change: ((tendered - due) * 100.0).round() / 100.0,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cash_payment() {
+        assert_eq!(round_to_nickel(7.22), 7.2);
+        assert_eq!(round_to_nickel(7.23), 7.25);
+        assert_eq!(round_to_nickel(7.275), 7.3);
+
+        assert_eq!(
+            CashPayment::new(7.23, 10.0),
+            Ok(CashPayment {
+                total: 7.23,
+                due: 7.25,
+                tendered: 10.0,
+                change: 2.75,
+            })
+        );
+        assert_eq!(CashPayment::new(7.23, 7.2), Err(7.25));
+    }
+}
@@ -0,0 +1,553 @@
+//! Importing and exporting the catalog as CSV, so the menu can be managed in a spreadsheet.
+//!
+//! Sales are written as `<date>: <price>`. The date is a weekday (`Fri`), several weekdays
+//! (`Sat+Sun`), a month (`Oct`), a month and day (`10-01`) or the last day of the month (`last`).
+//! Dates combine with `&` and `|`, where `&` binds tighter, and can be grouped in parentheses,
+//! e.g. `Fri & Oct | Sun`. The price is `8 for 6.00`, `25% off`, `two for one`,
+//! `buy one get one 50% off` or `0.25 each`.
+//!
+//! Allergens are separated by `;`. Nutrition facts aren't exported.
+
+use crate::{media, BulkPricing, Item, Sale, SaleDate, SalePrice};
+
+const HEADER: [&str; 12] = [
+    "id",
+    "name",
+    "imageURL",
+    "price",
+    "bulkAmount",
+    "bulkPrice",
+    "sale",
+    "ageRestricted",
+    "allergens",
+    "availableFrom",
+    "cost",
+    "floorPrice",
+];
+
+/// A row that couldn't be imported. `line` is 1-based and counts the header.
+#[derive(Debug, PartialEq)]
+pub struct CsvError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for CsvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for CsvError {}
+
+/// Reads a catalog, reporting every bad row rather than stopping at the first one.
+pub fn parse_csv(data: &str) -> Result<Vec<Item>, Vec<CsvError>> {
+    let mut records = read_records(data).into_iter();
+    let (line, header) = records.next().unwrap_or((1, vec![]));
+    if header != HEADER {
+        return Err(vec![CsvError {
+            line,
+            message: format!("expected the header `{}`", HEADER.join(",")),
+        }]);
+    }
+
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    for (line, record) in records {
+        match parse_record(&record) {
+            Ok(item) => items.push(item),
+            Err(message) => errors.push(CsvError { line, message }),
+        }
+    }
+    if errors.is_empty() {
+        Ok(items)
+    } else {
+        Err(errors)
+    }
+}
+
+pub fn to_csv(items: &[Item]) -> String {
+    let mut csv = HEADER.join(",") + "\n";
+    for item in items {
+        let (bulk_amount, bulk_price) = match &item.bulk_pricing {
+            Some(bulk_pricing) => (
+                bulk_pricing.amount.to_string(),
+                bulk_pricing.total_price.to_string(),
+            ),
+            None => (String::new(), String::new()),
+        };
+        let sale = item.sale.as_ref().map(format_sale).unwrap_or_default();
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        let record = [
+            item.id.to_string(),
+            item.name.clone(),
+            item.image_url.clone(),
+            item.price.to_string(),
+            bulk_amount,
+            bulk_price,
+            sale,
+            optional(item.age_restricted.map(|age| age.to_string())),
+            item.allergens.join(";"),
+            optional(item.available_from.map(|date| date.to_string())),
+            optional(item.cost.map(|cost| cost.to_string())),
+            optional(item.floor_price.map(|price| price.to_string())),
+        ];
+        let record: Vec<String> = record.iter().map(|field| quote(field)).collect();
+        csv += &record.join(",");
+        csv += "\n";
+    }
+    csv
+}
+
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Splits `data` into records, honouring quoted fields. Each record carries the line it starts on.
+fn read_records(data: &str) -> Vec<(usize, Vec<String>)> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' => in_quotes = !in_quotes,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes => {}
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                if record.iter().any(|field| !field.is_empty()) {
+                    records.push((start, std::mem::take(&mut record)));
+                }
+                record.clear();
+                line += 1;
+                start = line;
+            }
+            c => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    record.push(field);
+    if record.iter().any(|field| !field.is_empty()) {
+        records.push((start, record));
+    }
+    records
+}
+
+fn parse_record(record: &[String]) -> Result<Item, String> {
+    let wrong_length = || {
+        format!(
+            "expected {} columns but found {}",
+            HEADER.len(),
+            record.len()
+        )
+    };
+    let [id, name, image_url, price, bulk_amount, bulk_price, sale, details @ ..] = record else {
+        return Err(wrong_length());
+    };
+    let [age_restricted, allergens, available_from, cost, floor_price] = details else {
+        return Err(wrong_length());
+    };
+    let bulk_pricing = match (bulk_amount.as_str(), bulk_price.as_str()) {
+        ("", "") => None,
+        (amount, total_price) => {
+            let bulk_pricing = BulkPricing {
+                amount: parse_number("bulkAmount", amount)?,
+                total_price: parse_number("bulkPrice", total_price)?,
+            };
+            bulk_pricing.validate()?;
+            Some(bulk_pricing)
+        }
+    };
+    media::validate_image_url(image_url)?;
+    Ok(Item {
+        id: parse_number("id", id)?,
+        name: name.clone(),
+        image_url: image_url.clone(),
+        price: parse_number("price", price)?,
+        bulk_pricing,
+        sale: match sale.as_str() {
+            "" => None,
+            sale => Some(parse_sale(sale)?),
+        },
+        age_restricted: parse_optional("ageRestricted", age_restricted)?,
+        allergens: allergens
+            .split(';')
+            .map(str::trim)
+            .filter(|allergen| !allergen.is_empty())
+            .map(str::to_string)
+            .collect(),
+        nutrition: None,
+        available_from: parse_optional("availableFrom", available_from)?,
+        cost: parse_optional("cost", cost)?,
+        floor_price: parse_optional("floorPrice", floor_price)?,
+    })
+}
+
+fn parse_optional<T: std::str::FromStr>(column: &str, value: &str) -> Result<Option<T>, String> {
+    match value.trim() {
+        "" => Ok(None),
+        value => parse_number(column, value).map(Some),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(column: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("`{value}` is not a valid {column}"))
+}
+
+fn format_sale(sale: &Sale) -> String {
+    let sale_price = match &sale.sale_price {
+        SalePrice::QuantityForFixedPrice(quantity, price) => format!("{quantity} for {price:.2}"),
+        SalePrice::PercentageOff(discount) => format!("{}% off", format_percentage(*discount)),
+        SalePrice::TwoForOne => "two for one".to_string(),
+        SalePrice::BuyOneGetOnePercentOff(discount) => {
+            format!("buy one get one {}% off", format_percentage(*discount))
+        }
+        SalePrice::FixedUnitPrice(unit_price) => format!("{unit_price:.2} each"),
+    };
+    format!("{}: {sale_price}", format_date(&sale.date))
+}
+
+/// A fraction as the shortest percentage that reads back as exactly that fraction, so exporting
+/// and re-importing a sale doesn't change its discount.
+fn format_percentage(fraction: f64) -> String {
+    (0..=17)
+        .map(|precision| format!("{:.precision$}", fraction * 100.0))
+        .map(|percent| {
+            if percent.contains('.') {
+                percent
+                    .trim_end_matches('0')
+                    .trim_end_matches('.')
+                    .to_string()
+            } else {
+                percent
+            }
+        })
+        .find(|percent| percent.parse::<f64>().map(|percent| percent / 100.0) == Ok(fraction))
+        .unwrap_or_else(|| (fraction * 100.0).to_string())
+}
+
+fn format_date(date: &SaleDate) -> String {
+    match date {
+        SaleDate::MonthAndDay(month, day) => format!("{month:02}-{day:02}"),
+        SaleDate::LastDayOfMonth => "last".to_string(),
+        SaleDate::DayOfWeek(weekday) => weekday.to_string(),
+        SaleDate::Weekdays(weekdays) => {
+            let mut weekdays: Vec<_> = weekdays.iter().collect();
+            weekdays.sort_by_key(|weekday| weekday.num_days_from_monday());
+            let weekdays: Vec<String> =
+                weekdays.iter().map(|weekday| weekday.to_string()).collect();
+            weekdays.join("+")
+        }
+        SaleDate::Month(number) => match u8::try_from(*number).map(chrono::Month::try_from) {
+            Ok(Ok(month)) => month.name()[..3].to_string(),
+            _ => number.to_string(),
+        },
+        SaleDate::All(conditions) => conditions
+            .iter()
+            .map(|condition| match condition {
+                SaleDate::Any(_) => format!("({})", format_date(condition)),
+                condition => format_date(condition),
+            })
+            .collect::<Vec<_>>()
+            .join(" & "),
+        SaleDate::Any(conditions) => conditions
+            .iter()
+            .map(format_date)
+            .collect::<Vec<_>>()
+            .join(" | "),
+    }
+}
+
+fn parse_sale(sale: &str) -> Result<Sale, String> {
+    let invalid = || format!("`{sale}` is not a valid sale");
+    let (date, sale_price) = sale.split_once(':').ok_or_else(invalid)?;
+    let date = parse_date(date).ok_or_else(invalid)?;
+    let sale_price = sale_price.trim();
+    let sale_price = if sale_price == "two for one" {
+        SalePrice::TwoForOne
+    } else if let Some(percentage) = sale_price
+        .strip_prefix("buy one get one ")
+        .and_then(|sale_price| sale_price.strip_suffix("% off"))
+    {
+        let percentage: f64 = percentage.trim().parse().map_err(|_| invalid())?;
+        SalePrice::BuyOneGetOnePercentOff(percentage / 100.0)
+    } else if let Some(unit_price) = sale_price.strip_suffix(" each") {
+        SalePrice::FixedUnitPrice(unit_price.trim().parse().map_err(|_| invalid())?)
+    } else if let Some(percentage) = sale_price.strip_suffix("% off") {
+        let percentage: f64 = percentage.trim().parse().map_err(|_| invalid())?;
+        SalePrice::PercentageOff(percentage / 100.0)
+    } else if let Some((quantity, price)) = sale_price.split_once(" for ") {
+        SalePrice::QuantityForFixedPrice(
+            quantity.trim().parse().map_err(|_| invalid())?,
+            price.trim().parse().map_err(|_| invalid())?,
+        )
+    } else {
+        return Err(invalid());
+    };
+    let parsed = Sale { date, sale_price };
+    parsed
+        .validate()
+        .map_err(|err| format!("`{sale}`: {err}"))?;
+    Ok(parsed)
+}
+
+fn parse_date(date: &str) -> Option<SaleDate> {
+    let date = date.trim();
+    let any = split_top_level(date, '|')?;
+    if any.len() > 1 {
+        return any
+            .into_iter()
+            .map(parse_date)
+            .collect::<Option<_>>()
+            .map(SaleDate::Any);
+    }
+    let all = split_top_level(date, '&')?;
+    if all.len() > 1 {
+        return all
+            .into_iter()
+            .map(parse_date)
+            .collect::<Option<_>>()
+            .map(SaleDate::All);
+    }
+    if let Some(grouped) = date
+        .strip_prefix('(')
+        .and_then(|date| date.strip_suffix(')'))
+    {
+        return parse_date(grouped);
+    }
+    if date == "last" {
+        Some(SaleDate::LastDayOfMonth)
+    } else if let Some((month, day)) = date.split_once('-') {
+        Some(SaleDate::MonthAndDay(
+            month.parse().ok()?,
+            day.parse().ok()?,
+        ))
+    } else if date.contains('+') {
+        date.split('+')
+            .map(|weekday| weekday.trim().parse().ok())
+            .collect::<Option<_>>()
+            .map(SaleDate::Weekdays)
+    } else if let Ok(weekday) = date.parse() {
+        Some(SaleDate::DayOfWeek(weekday))
+    } else {
+        let month: chrono::Month = date.parse().ok()?;
+        Some(SaleDate::Month(month.number_from_month()))
+    }
+}
+
+/// Splits on `separator` outside parentheses, or returns `None` if they're unbalanced.
+fn split_top_level(text: &str, separator: char) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let mut depth = 0_usize;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1)?,
+            c if c == separator && depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    (depth == 0).then_some(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{CatalogBuilder, ItemBuilder};
+
+    #[test]
+    fn test_csv_round_trip() {
+        let mut items = CatalogBuilder::bakery_demo().build();
+        items.push(
+            ItemBuilder::new(5, "Cookie, Oatmeal \"Raisin\"", 1.5)
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                    SalePrice::QuantityForFixedPrice(8, 6.0),
+                )
+                .build(),
+        );
+        items[1].sale = Some(Sale {
+            date: SaleDate::MonthAndDay(10, 1),
+            sale_price: SalePrice::PercentageOff(0.25),
+        });
+        items[2].sale = Some(Sale {
+            date: SaleDate::All(vec![
+                SaleDate::Month(10),
+                SaleDate::Any(vec![
+                    SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                    SaleDate::MonthAndDay(12, 24),
+                ]),
+            ]),
+            sale_price: SalePrice::PercentageOff(0.1),
+        });
+        items[3].sale = Some(Sale {
+            date: SaleDate::Weekdays([chrono::Weekday::Sun, chrono::Weekday::Sat].into()),
+            sale_price: SalePrice::TwoForOne,
+        });
+        items[0].sale = Some(Sale {
+            date: SaleDate::LastDayOfMonth,
+            sale_price: SalePrice::PercentageOff(0.5),
+        });
+        items.push(
+            ItemBuilder::new(6, "Eclair", 3.0)
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Wed),
+                    SalePrice::BuyOneGetOnePercentOff(0.5),
+                )
+                .build(),
+        );
+        items.push(
+            ItemBuilder::new(7, "Cruller", 1.0)
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Tue),
+                    SalePrice::FixedUnitPrice(0.25),
+                )
+                .build(),
+        );
+
+        items.push(
+            ItemBuilder::new(8, "Rum Cake", 12.0)
+                .with_age_restriction(21)
+                .with_allergens(&["eggs", "tree nuts"])
+                .with_available_from(chrono::NaiveDate::from_ymd_opt(2021, 12, 1).unwrap())
+                .with_cost(4.5)
+                .with_floor_price(10.0)
+                .build(),
+        );
+        items.push(
+            ItemBuilder::new(9, "Scone", 1.25)
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Thu),
+                    SalePrice::PercentageOff(0.07),
+                )
+                .build(),
+        );
+        items.push(
+            ItemBuilder::new(11, "Pretzel", 2.0)
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                    SalePrice::PercentageOff(0.0725),
+                )
+                .build(),
+        );
+        items.push(
+            ItemBuilder::new(10, "Bagel", 1.0)
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Mon),
+                    SalePrice::BuyOneGetOnePercentOff(0.125),
+                )
+                .build(),
+        );
+
+        let csv = to_csv(&items);
+        assert!(csv.contains("\n2,Key Lime Cheesecake,"));
+        assert!(csv.contains(",8,,,10-01: 25% off,"));
+        assert!(csv.contains(",0.5,,,Sat+Sun: two for one,"));
+        assert!(csv.contains(",4,7,last: 50% off,"));
+        assert!(csv.contains(",3,,,Wed: buy one get one 50% off,"));
+        assert!(csv.contains(",1,,,Tue: 0.25 each,"));
+        assert!(csv.contains(",6,6,Oct & (Fri | 12-24): 10% off,"));
+        assert!(csv.contains("\n5,\"Cookie, Oatmeal \"\"Raisin\"\"\","));
+        assert!(csv.contains(",12,,,,21,eggs;tree nuts,2021-12-01,4.5,10\n"));
+        assert!(csv.contains(",Thu: 7% off,"));
+        assert!(csv.contains(",Mon: buy one get one 12.5% off,"));
+        assert!(csv.contains(",Fri: 7.25% off,"));
+
+        let parsed = parse_csv(&csv).unwrap();
+        assert_eq!(parsed.len(), 11);
+        assert!(matches!(
+            parsed[9].sale.as_ref().unwrap().sale_price,
+            SalePrice::PercentageOff(discount) if discount == 0.0725
+        ));
+        assert_eq!(parsed[7].age_restricted, Some(21));
+        assert_eq!(parsed[7].allergens, ["eggs", "tree nuts"]);
+        assert_eq!(to_csv(&parsed), csv);
+    }
+
+    #[test]
+    fn test_csv_row_errors() {
+        let csv = "id,name,imageURL,price,bulkAmount,bulkPrice,sale,ageRestricted,allergens,availableFrom,cost,floorPrice
+1,Brownie,https://example.com/brownie.jpg,2.0,4,7.0,,,,,,
+2,Key Lime Cheesecake,https://example.com/cheesecake.jpg,eight,,,,,,,,
+3,Cookie,https://example.com/cookie.jpg,1.25,6,6.0,Caturday: two for one,,,,,
+4,Mini Gingerbread Donut,https://example.com/donut.jpg
+5,Croissant,https://example.com/croissant.jpg,3.0,,,04-31: 25% off,,,,,
+6,Eclair,https://example.com/eclair.jpg,3.0,,,Fri: 120% off,,,,,
+7,Rum Cake,file:///etc/passwd,12.0,,,,21,,,,
+8,Wine Cake,https://example.com/wine-cake.jpg,12.0,,,,adults,,,,
+9,Muffin,https://example.com/muffin.jpg,2.5,0,5.0,,,,,,
+10,Scone,https://example.com/scone.jpg,2.5,,,Fri: 0 for 5.00,,,,,
+";
+        assert_eq!(
+            parse_csv(csv).unwrap_err(),
+            vec![
+                CsvError {
+                    line: 3,
+                    message: "`eight` is not a valid price".to_string(),
+                },
+                CsvError {
+                    line: 4,
+                    message: "`Caturday: two for one` is not a valid sale".to_string(),
+                },
+                CsvError {
+                    line: 5,
+                    message: "expected 12 columns but found 3".to_string(),
+                },
+                CsvError {
+                    line: 6,
+                    message: "`04-31: 25% off`: the sale is on a date that never occurs"
+                        .to_string(),
+                },
+                CsvError {
+                    line: 7,
+                    message:
+                        "`Fri: 120% off`: the sale is 120% off, which isn't between 0% and 100%"
+                            .to_string(),
+                },
+                CsvError {
+                    line: 8,
+                    message: "image URL `file:///etc/passwd` uses unsupported scheme `file`"
+                        .to_string(),
+                },
+                CsvError {
+                    line: 9,
+                    message: "`adults` is not a valid ageRestricted".to_string(),
+                },
+                CsvError {
+                    line: 10,
+                    message: "bulk pricing needs groups of at least 1 item".to_string(),
+                },
+                CsvError {
+                    line: 11,
+                    message: "`Fri: 0 for 5.00`: the sale is on groups of 0 items".to_string(),
+                },
+            ]
+        );
+
+        assert_eq!(parse_csv("name,price\n").unwrap_err()[0].line, 1);
+    }
+}
@@ -0,0 +1,197 @@
+//! Keeps each cart as a JSON file under a data directory, so the demo can run with no external
+//! services.
+
+use crate::{CartLimits, PriceOverride, ShoppingCart, SortKey};
+use serde::{Deserialize, Serialize};
+use std::io::{Error, ErrorKind};
+
+/// A cart as saved, owning its product names so lines for items that have since left the catalog
+/// are kept for `ShoppingCart::reprice` to report.
+#[derive(Deserialize, Serialize)]
+pub struct StoredCart {
+    /// In the order they were added.
+    lines: Vec<StoredLine>,
+    pickup: Option<chrono::NaiveDateTime>,
+    /// Carts saved before limits were kept get the defaults.
+    #[serde(default)]
+    limits: CartLimits,
+}
+
+impl StoredCart {
+    /// The saved cart, borrowing its product names from this. It was within its limits when
+    /// saved, so they aren't checked again.
+    pub fn cart(&self) -> ShoppingCart<'_> {
+        let mut cart = ShoppingCart::new();
+        cart.pickup = self.pickup;
+        cart.set_limits(self.limits);
+        for line in &self.lines {
+            let product = line.product.as_str();
+            cart.next_added += 1;
+            cart.added.insert(product, cart.next_added);
+            cart.products.insert(product, line.quantity);
+            if let Some(price_override) = &line.price_override {
+                cart.price_overrides.insert(product, price_override.clone());
+            }
+        }
+        cart
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct StoredLine {
+    product: String,
+    quantity: usize,
+    price_override: Option<PriceOverride>,
+}
+
+pub struct FileStore {
+    dir: std::path::PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: impl Into<std::path::PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, id: &str) -> std::io::Result<std::path::PathBuf> {
+        let valid = !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("`{id}` is not a valid cart ID"),
+            ));
+        }
+        Ok(self.dir.join(format!("{id}.json")))
+    }
+
+    pub fn save(&self, id: &str, cart: &ShoppingCart) -> std::io::Result<()> {
+        let lines = cart
+            .items_sorted(&[], SortKey::AddedAt)
+            .into_iter()
+            .map(|(product, quantity)| StoredLine {
+                product: product.to_string(),
+                quantity,
+                price_override: cart.price_overrides.get(product).cloned(),
+            })
+            .collect();
+        let stored = StoredCart {
+            lines,
+            pickup: cart.pickup,
+            limits: cart.limits,
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        let file = std::io::BufWriter::new(std::fs::File::create(self.path(id)?)?);
+        serde_json::to_writer_pretty(file, &stored)?;
+        Ok(())
+    }
+
+    /// Loads a saved cart. Use `StoredCart::cart` to get at it as a `ShoppingCart`.
+    pub fn load(&self, id: &str) -> std::io::Result<StoredCart> {
+        let file = std::io::BufReader::new(std::fs::File::open(self.path(id)?)?);
+        Ok(serde_json::from_reader(file)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::access::Role;
+    use crate::fixtures::{CatalogBuilder, ItemBuilder};
+    use crate::{Catalog, RepriceAdjustment};
+
+    #[test]
+    fn test_file_store_round_trip() {
+        let catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+        let store = FileStore::new(std::env::temp_dir().join("shopping-cart-test-file-store"));
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 7).unwrap();
+        cart.add("Brownie", 4).unwrap();
+        cart.set_line_price_override(Role::Manager, "Brownie", 1.0, "day old")
+            .unwrap();
+        cart.set_pickup(
+            chrono::NaiveDate::from_ymd_opt(2021, 10, 1)
+                .unwrap()
+                .and_hms_opt(9, 0, 0)
+                .unwrap(),
+        );
+        store.save("cart-1", &cart).unwrap();
+
+        let stored = store.load("cart-1").unwrap();
+        let loaded = stored.cart();
+        assert_eq!(
+            loaded.items_sorted(&catalog.items, SortKey::AddedAt),
+            vec![("Cookie", 7), ("Brownie", 4)]
+        );
+        assert_eq!(loaded.pickup, cart.pickup);
+        assert_eq!(loaded.price_overrides["Brownie"].reason, "day old");
+        assert_eq!(loaded.total(&catalog, dummy_date), 11.25);
+
+        assert_eq!(
+            store.load("../cart-1").err().map(|err| err.kind()),
+            Some(ErrorKind::InvalidInput)
+        );
+        assert_eq!(
+            store.load("cart-2").err().map(|err| err.kind()),
+            Some(ErrorKind::NotFound)
+        );
+    }
+
+    #[test]
+    fn test_file_store_keeps_what_the_catalog_wouldnt_allow_now() {
+        let names: Vec<String> = (1..=150).map(|id| format!("Wholesale {id}")).collect();
+        let mut builder = CatalogBuilder::bakery_demo();
+        for (id, name) in (100..).zip(&names) {
+            builder = builder.with_item(ItemBuilder::new(id, name, 1.0));
+        }
+        let mut catalog = Catalog::new(builder.build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+        let store = FileStore::new(std::env::temp_dir().join("shopping-cart-test-file-store"));
+
+        let mut cart = ShoppingCart::new();
+        cart.set_limits(CartLimits {
+            max_lines: 200,
+            max_units_per_line: 5_000,
+            ..CartLimits::default()
+        });
+        for name in &names {
+            cart.add(name, 2_000).unwrap();
+        }
+        cart.add("Cookie", 1).unwrap();
+        cart.add("Brownie", 1).unwrap();
+        store.save("wholesale", &cart).unwrap();
+
+        catalog
+            .soft_delete_item(
+                crate::access::Role::Admin,
+                3,
+                chrono::NaiveDateTime::default(),
+            )
+            .unwrap();
+        catalog.remove_item(crate::access::Role::Admin, 1).unwrap();
+        let stored = store.load("wholesale").unwrap();
+        let mut loaded = stored.cart();
+        assert_eq!(loaded.products.len(), 152);
+        assert_eq!(loaded.limits.max_lines, 200);
+        assert_eq!(
+            loaded.items_sorted(&catalog.items, SortKey::AddedAt)[150..],
+            [("Cookie", 1), ("Brownie", 1)]
+        );
+        assert_eq!(loaded.add("Wholesale 1", 3_000), Ok(()));
+        assert_eq!(
+            loaded.reprice(&catalog, dummy_date, false),
+            [
+                RepriceAdjustment::Unavailable {
+                    product: "Brownie".to_string(),
+                },
+                RepriceAdjustment::TemporarilyUnavailable {
+                    product: "Cookie".to_string(),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,135 @@
+//! Builders for the items and catalogs used in tests, so each test only spells out what it
+//! actually depends on.
+
+use crate::{BulkPricing, Item, Sale, SaleDate, SalePrice};
+
+pub struct ItemBuilder {
+    item: Item,
+}
+
+impl ItemBuilder {
+    pub fn new(id: u32, name: &str, price: f64) -> Self {
+        Self {
+            item: Item {
+                id,
+                name: name.to_string(),
+                image_url: format!(
+                    "https://example.com/{}.jpg",
+                    name.to_lowercase().replace(' ', "-")
+                ),
+                price,
+                bulk_pricing: None,
+                sale: None,
+                age_restricted: None,
+                allergens: vec![],
+                nutrition: None,
+                available_from: None,
+                cost: None,
+                floor_price: None,
+            },
+        }
+    }
+
+    pub fn brownie() -> Self {
+        Self::new(1, "Brownie", 2.0).with_image_url(
+            "https://encrypted-tbn0.gstatic.com/images?q=tbn:ANd9GcTHdr1eTXEMs68Dx-b_mZT0RpifEQ8so6A1unRsJlyJIPe0LUE2HQ",
+        )
+    }
+
+    pub fn key_lime_cheesecake() -> Self {
+        Self::new(2, "Key Lime Cheesecake", 8.0).with_image_url(
+            "http://1.bp.blogspot.com/-7we9Z0C_fpI/T90JXcg3YsI/AAAAAAAABn4/EN7u2vMuRug/s1600/key+lime+cheesecake+slice+in+front.jpg",
+        )
+    }
+
+    pub fn cookie() -> Self {
+        Self::new(3, "Cookie", 1.25).with_image_url(
+            "http://www.mayheminthekitchen.com/wp-content/uploads/2015/05/chocolate-cookie-square.jpg",
+        )
+    }
+
+    pub fn mini_gingerbread_donut() -> Self {
+        Self::new(4, "Mini Gingerbread Donut", 0.5).with_image_url(
+            "https://i.etsystatic.com/29050134/r/il/634971/3087380231/il_794xN.3087380231_n32u.jpg",
+        )
+    }
+
+    pub fn with_image_url(mut self, image_url: &str) -> Self {
+        self.item.image_url = image_url.to_string();
+        self
+    }
+
+    pub fn with_bulk(mut self, amount: u32, total_price: f64) -> Self {
+        self.item.bulk_pricing = Some(BulkPricing {
+            amount,
+            total_price,
+        });
+        self
+    }
+
+    pub fn with_sale(mut self, date: SaleDate, sale_price: SalePrice) -> Self {
+        self.item.sale = Some(Sale { date, sale_price });
+        self
+    }
+
+    pub fn with_age_restriction(mut self, minimum_age: u8) -> Self {
+        self.item.age_restricted = Some(minimum_age);
+        self
+    }
+
+    pub fn with_allergens(mut self, allergens: &[&str]) -> Self {
+        self.item.allergens = allergens
+            .iter()
+            .map(|allergen| allergen.to_string())
+            .collect();
+        self
+    }
+
+    pub fn with_available_from(mut self, available_from: chrono::NaiveDate) -> Self {
+        self.item.available_from = Some(available_from);
+        self
+    }
+
+    pub fn with_cost(mut self, cost: f64) -> Self {
+        self.item.cost = Some(cost);
+        self
+    }
+
+    pub fn with_floor_price(mut self, floor_price: f64) -> Self {
+        self.item.floor_price = Some(floor_price);
+        self
+    }
+
+    pub fn build(self) -> Item {
+        self.item
+    }
+}
+
+#[derive(Default)]
+pub struct CatalogBuilder {
+    items: Vec<Item>,
+}
+
+impl CatalogBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The four treats from `products-data.json`.
+    pub fn bakery_demo() -> Self {
+        Self::new()
+            .with_item(ItemBuilder::brownie().with_bulk(4, 7.0))
+            .with_item(ItemBuilder::key_lime_cheesecake())
+            .with_item(ItemBuilder::cookie().with_bulk(6, 6.0))
+            .with_item(ItemBuilder::mini_gingerbread_donut())
+    }
+
+    pub fn with_item(mut self, item: ItemBuilder) -> Self {
+        self.items.push(item.build());
+        self
+    }
+
+    pub fn build(self) -> Vec<Item> {
+        self.items
+    }
+}
@@ -1,49 +1,952 @@
+// Most of this is an API for a storefront to call, and `main` only demos a little of it. The tests
+// cover the rest, so dead code is still caught there.
+#![cfg_attr(not(test), allow(dead_code))]
+
 use chrono::{Datelike, Weekday};
 use serde::{Deserialize, Serialize};
 use serde_json::Result;
 
+mod access;
+mod audit;
+mod capacity;
+mod cash;
+mod csv;
+mod file_store;
+#[cfg(test)]
+mod fixtures;
+mod media;
+mod pricing;
+mod slots;
+
 struct ShoppingCart<'a> {
     products: std::collections::HashMap<&'a str, usize>,
+    /// When the customer will pick up or receive the order, if they chose a slot.
+    pickup: Option<chrono::NaiveDateTime>,
+    /// The customer's age, once it has been checked against an ID.
+    verified_age: Option<u8>,
+    limits: CartLimits,
+    /// The catalog version this cart was last priced against.
+    catalog_version: Option<u64>,
+    /// The unit price of each line when the cart was last priced.
+    priced_at: std::collections::HashMap<&'a str, f64>,
+    /// What changed the last time the cart was repriced, for display to the customer.
+    adjustments: Vec<RepriceAdjustment>,
+    /// Unit prices set by staff that replace normal pricing for a line, e.g. for damaged goods.
+    price_overrides: std::collections::HashMap<&'a str, PriceOverride>,
+    /// The order lines were added in, for listing them that way.
+    added: std::collections::HashMap<&'a str, usize>,
+    next_added: usize,
+    /// Who changed the cart, oldest first.
+    audit_log: audit::AuditLog,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum SortKey {
+    Name,
+    /// Cheapest unit price first.
+    Price,
+    AddedAt,
+}
+
+/// One priced line of a cart, from `ShoppingCart::breakdown`.
+#[derive(Debug, PartialEq, Serialize)]
+struct LineBreakdown {
+    product: String,
+    quantity: usize,
+    total: f64,
+    /// E.g. "6 @ bulk 6-for-$6.00, 2 @ $1.25".
+    explanation: String,
+}
+
+/// An at-a-glance view of a cart for dashboards and the mini-cart widget.
+#[derive(Debug, PartialEq)]
+struct CartSummary {
+    distinct_products: usize,
+    total_units: usize,
+    /// Sales running on the pricing date plus cross-line promotions, that some line in the cart
+    /// is eligible for.
+    applicable_promotions: usize,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct PriceOverride {
+    price: f64,
+    reason: String,
+}
+
+#[derive(Debug, PartialEq)]
+enum RepriceAdjustment {
+    /// The item left the catalog and its line was removed from the cart.
+    Removed {
+        product: String,
+    },
+    /// The item left the catalog but its line was kept; it isn't charged.
+    Unavailable {
+        product: String,
+    },
+    /// The item is in the catalog's trash and may be restored, so its line is kept even when
+    /// missing items are removed. It isn't charged.
+    TemporarilyUnavailable {
+        product: String,
+    },
+    PriceChanged {
+        product: String,
+        was: f64,
+        now: f64,
+    },
+}
+
+/// Guards against fat-finger orders like 10,000 donuts.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+struct CartLimits {
+    max_lines: usize,
+    max_units_per_line: usize,
+    max_order_value: f64,
+}
+
+impl Default for CartLimits {
+    fn default() -> Self {
+        Self {
+            max_lines: 100,
+            max_units_per_line: 1_000,
+            max_order_value: 10_000.0,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum CartError {
+    AgeVerificationRequired {
+        product: String,
+        minimum_age: u8,
+    },
+    TooManyLines {
+        max: usize,
+    },
+    NotInCart {
+        product: String,
+    },
+    PermissionDenied(access::PermissionDenied),
+    TooManyUnits {
+        product: String,
+        max: usize,
+    },
+    OrderValueExceeded {
+        total: f64,
+        max: f64,
+    },
+    SoldOutForDate {
+        product: String,
+        date: chrono::NaiveDate,
+    },
+    InsufficientTender {
+        due: f64,
+        tendered: f64,
+    },
+    NotYetAvailable {
+        product: String,
+        available_from: chrono::NaiveDate,
+    },
+    PickupRequired,
+    SlotFull {
+        pickup: chrono::NaiveDateTime,
+    },
+    InvalidPrice {
+        price: f64,
+    },
+    Unavailable {
+        product: String,
+    },
+    /// The item is in the catalog's trash and may be restored.
+    TemporarilyUnavailable {
+        product: String,
+    },
+}
+
+impl std::fmt::Display for CartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CartError::AgeVerificationRequired {
+                product,
+                minimum_age,
+            } => write!(
+                f,
+                "{product} requires the customer to be verified as at least {minimum_age} years old"
+            ),
+            CartError::TooManyLines { max } => {
+                write!(f, "a cart can't hold more than {max} different products")
+            }
+            CartError::NotInCart { product } => write!(f, "{product} is not in the cart"),
+            CartError::PermissionDenied(denied) => denied.fmt(f),
+            CartError::TooManyUnits { product, max } => {
+                write!(f, "can't add more than {max} of {product} to a cart")
+            }
+            CartError::OrderValueExceeded { total, max } => {
+                write!(
+                    f,
+                    "an order of {total} exceeds the maximum order value of {max}"
+                )
+            }
+            CartError::SoldOutForDate { product, date } => {
+                write!(f, "no more {product} can be made for {date}")
+            }
+            CartError::InsufficientTender { due, tendered } => {
+                write!(f, "{tendered} tendered doesn't cover the {due} due")
+            }
+            CartError::NotYetAvailable {
+                product,
+                available_from,
+            } => write!(
+                f,
+                "{product} is a pre-order and can't be picked up before {available_from}"
+            ),
+            CartError::PickupRequired => write!(f, "choose a pickup time before checking out"),
+            CartError::SlotFull { pickup } => {
+                write!(f, "there is no pickup slot with room left at {pickup}")
+            }
+            CartError::InvalidPrice { price } => {
+                write!(f, "{price} isn't a valid price")
+            }
+            CartError::Unavailable { product } => write!(f, "{product} is no longer sold"),
+            CartError::TemporarilyUnavailable { product } => {
+                write!(f, "{product} is temporarily unavailable")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CartError {}
+
+impl From<access::PermissionDenied> for CartError {
+    fn from(denied: access::PermissionDenied) -> Self {
+        CartError::PermissionDenied(denied)
+    }
 }
 
 impl<'a> ShoppingCart<'a> {
     fn new() -> Self {
         Self {
             products: std::collections::HashMap::new(),
+            pickup: None,
+            verified_age: None,
+            limits: CartLimits::default(),
+            catalog_version: None,
+            priced_at: std::collections::HashMap::new(),
+            adjustments: Vec::new(),
+            price_overrides: std::collections::HashMap::new(),
+            added: std::collections::HashMap::new(),
+            next_added: 0,
+            audit_log: audit::AuditLog::default(),
+        }
+    }
+
+    fn add(&mut self, product: &'a str, quantity: usize) -> std::result::Result<(), CartError> {
+        if quantity > self.limits.max_units_per_line {
+            return Err(CartError::TooManyUnits {
+                product: product.to_string(),
+                max: self.limits.max_units_per_line,
+            });
+        }
+        if !self.products.contains_key(product) && self.products.len() >= self.limits.max_lines {
+            return Err(CartError::TooManyLines {
+                max: self.limits.max_lines,
+            });
+        }
+        let before = self.products.insert(product, quantity);
+        self.audit_log.record(
+            audit::Actor::Customer,
+            "add",
+            product,
+            before.as_ref().and_then(audit::snapshot),
+            audit::snapshot(&quantity),
+        );
+        self.added.entry(product).or_insert_with(|| {
+            self.next_added += 1;
+            self.next_added
+        });
+        Ok(())
+    }
+
+    fn remove_line(&mut self, actor: audit::Actor, product: &str) {
+        if let Some(quantity) = self.products.remove(product) {
+            self.audit_log.record(
+                actor,
+                "remove_line",
+                product,
+                audit::snapshot(&quantity),
+                None,
+            );
+        }
+        self.priced_at.remove(product);
+        self.price_overrides.remove(product);
+        self.added.remove(product);
+    }
+
+    /// The cart's lines as `(product, quantity)`, ordered by `key`. Lines for items missing from
+    /// `items` sort last when ordering by price.
+    fn items_sorted(&self, items: &[Item], key: SortKey) -> Vec<(&'a str, usize)> {
+        let mut lines: Vec<(&'a str, usize)> = self
+            .products
+            .iter()
+            .map(|(product, quantity)| (*product, *quantity))
+            .collect();
+        match key {
+            SortKey::Name => lines.sort_unstable_by_key(|(product, _)| *product),
+            SortKey::Price => {
+                let index = index_items(items);
+                let price =
+                    |product: &str| index.get(product).map_or(f64::INFINITY, |item| item.price);
+                lines.sort_by(|(a, _), (b, _)| price(a).total_cmp(&price(b)).then(a.cmp(b)));
+            }
+            SortKey::AddedAt => lines.sort_unstable_by_key(|(product, _)| {
+                (
+                    self.added.get(product).copied().unwrap_or(usize::MAX),
+                    *product,
+                )
+            }),
+        }
+        lines
+    }
+
+    /// One page of `items_sorted`, so a large wholesale cart can be shown a page at a time.
+    fn items_page(
+        &self,
+        items: &[Item],
+        key: SortKey,
+        offset: usize,
+        limit: usize,
+    ) -> Vec<(&'a str, usize)> {
+        let mut lines = self.items_sorted(items, key);
+        lines.truncate(offset.saturating_add(limit));
+        lines.split_off(offset.min(lines.len()))
+    }
+
+    /// Charges `price` per unit for `product` instead of its normal price, sales and bulk deals.
+    fn set_line_price_override(
+        &mut self,
+        role: access::Role,
+        product: &str,
+        price: f64,
+        reason: &str,
+    ) -> std::result::Result<(), CartError> {
+        role.authorize(access::Permission::OverridePrice)?;
+        if !price.is_finite() || price < 0.0 {
+            return Err(CartError::InvalidPrice { price });
+        }
+        let Some((product, _)) = self.products.get_key_value(product) else {
+            return Err(CartError::NotInCart {
+                product: product.to_string(),
+            });
+        };
+        let price_override = PriceOverride {
+            price,
+            reason: reason.to_string(),
+        };
+        let after = audit::snapshot(&price_override);
+        let before = self.price_overrides.insert(product, price_override);
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "set_line_price_override",
+            *product,
+            before.as_ref().and_then(audit::snapshot),
+            after,
+        );
+        Ok(())
+    }
+
+    fn set_limits(&mut self, limits: CartLimits) {
+        self.limits = limits;
+    }
+
+    fn set_pickup(&mut self, pickup: chrono::NaiveDateTime) {
+        self.pickup = Some(pickup);
+    }
+
+    fn set_verified_age(&mut self, age: u8) {
+        self.verified_age = Some(age);
+    }
+
+    /// Fails if any line in the cart is age restricted and the customer's age hasn't been verified
+    /// as old enough. Lines for items that are no longer in the catalog are skipped.
+    fn check_age(&self, items: &[Item]) -> std::result::Result<(), CartError> {
+        let items_by_name = index_items(items);
+        for product in self.products.keys() {
+            let Some(item) = items_by_name.get(product) else {
+                continue;
+            };
+            if let Some(minimum_age) = item.age_restricted {
+                if self.verified_age.is_none_or(|age| age < minimum_age) {
+                    return Err(CartError::AgeVerificationRequired {
+                        product: item.name.clone(),
+                        minimum_age,
+                    });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if any line is for an item that's no longer in the catalog, so it can't be bought
+    /// for nothing.
+    fn check_in_catalog(&self, catalog: &Catalog) -> std::result::Result<(), CartError> {
+        let items_by_name = index_items(&catalog.items);
+        let mut products: Vec<&str> = self.products.keys().copied().collect();
+        products.sort_unstable();
+        match products
+            .into_iter()
+            .find(|product| !items_by_name.contains_key(product))
+        {
+            Some(product) if catalog.trash.iter().any(|(item, _)| item.name == product) => {
+                Err(CartError::TemporarilyUnavailable {
+                    product: product.to_string(),
+                })
+            }
+            Some(product) => Err(CartError::Unavailable {
+                product: product.to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Lines for items that aren't available yet on `date`, with the date each becomes available.
+    fn preorders(
+        &self,
+        items: &[Item],
+        date: &chrono::NaiveDate,
+    ) -> Vec<(&'a str, chrono::NaiveDate)> {
+        let items_by_name = index_items(items);
+        let mut preorders: Vec<_> = self
+            .products
+            .keys()
+            .filter_map(|product| {
+                let available_from = items_by_name.get(product)?.available_from?;
+                (available_from > *date).then_some((*product, available_from))
+            })
+            .collect();
+        preorders.sort();
+        preorders
+    }
+
+    /// Fails if a pre-ordered line can't be ready by the pickup date, or by `date` when no pickup
+    /// is set.
+    fn check_availability(
+        &self,
+        items: &[Item],
+        date: &chrono::NaiveDate,
+    ) -> std::result::Result<(), CartError> {
+        match self.preorders(items, &self.pricing_date(date)).first() {
+            Some((product, available_from)) => Err(CartError::NotYetAvailable {
+                product: product.to_string(),
+                available_from: *available_from,
+            }),
+            None => Ok(()),
         }
     }
 
-    fn add(&mut self, product: &'a str, quantity: usize) {
-        self.products.insert(product, quantity);
+    /// Lines whose discounts were held back by the item's floor price, with how much was held back,
+    /// so support can explain why a deal didn't fully apply.
+    fn floor_clamps(&self, catalog: &Catalog, date: &chrono::NaiveDate) -> Vec<(&'a str, f64)> {
+        let date = self.pricing_date(date);
+        let rescheduled = catalog.rescheduled_items(&date);
+        let items_by_name = catalog.index_on(&rescheduled);
+        let mut clamps: Vec<_> = self
+            .products
+            .iter()
+            .filter(|(product, _)| !self.price_overrides.contains_key(*product))
+            .filter_map(|(product, quantity)| {
+                let item = items_by_name.get(product)?;
+                let held_back = Self::line_total(item, *quantity, &date)
+                    - Self::discounted_line_total(item, *quantity, &date);
+                (held_back > 0.0).then_some((*product, held_back))
+            })
+            .collect();
+        clamps.sort_by(|a, b| a.0.cmp(b.0));
+        clamps
     }
 
-    fn total(&self, items: &[Item], date: &chrono::NaiveDate) -> f64 {
-        let mut total = 0.0;
+    /// What the cart sells for less what it costs to make, including anything promotions add, or
+    /// `None` if an item's cost is unknown.
+    fn gross_margin(&self, catalog: &Catalog, date: &chrono::NaiveDate) -> Option<f64> {
+        let items_by_name = index_items(&catalog.items);
+        let mut cost = 0.0;
         for (product, quantity) in &self.products {
-            let item = items.iter().find(|item| item.name == *product).unwrap();
-            total += match &item.sale {
-                Some(sale) => match &sale.date {
-                    SaleDate::DayOfWeek(weekday) if date.weekday() == *weekday => {
-                        Self::apply_sale_price(&sale.sale_price, *quantity, item.price)
-                    }
-                    SaleDate::MonthAndDay(month, day)
-                        if date.month() == *month && date.day() == *day =>
-                    {
-                        Self::apply_sale_price(&sale.sale_price, *quantity, item.price)
+            cost += items_by_name.get(product)?.cost? * *quantity as f64;
+        }
+        for line in self.promotional_lines(catalog, date) {
+            let reward = catalog.items.iter().find(|item| item.id == line.item_id)?;
+            cost += reward.cost?;
+        }
+        Some(self.total(catalog, date) - cost)
+    }
+
+    /// Every allergen in the cart, so customers can be warned before checkout.
+    fn allergen_summary<'i>(&self, items: &'i [Item]) -> std::collections::BTreeSet<&'i str> {
+        let items_by_name = index_items(items);
+        let mut allergens = std::collections::BTreeSet::new();
+        for product in self.products.keys() {
+            let Some(item) = items_by_name.get(product) else {
+                continue;
+            };
+            allergens.extend(item.allergens.iter().map(String::as_str));
+        }
+        allergens
+    }
+
+    /// Sales and scheduled prices are evaluated against the pickup date when one is set, and
+    /// against `date` otherwise, so ordering on Thursday night for a Friday pickup still gets the
+    /// Friday deals.
+    fn total(&self, catalog: &Catalog, date: &chrono::NaiveDate) -> f64 {
+        let rescheduled = catalog.rescheduled_items(&self.pricing_date(date));
+        self.total_indexed(&catalog.index_on(&rescheduled), &catalog.promotions, date)
+    }
+
+    /// Like `total`, but reuses an index built by `Catalog::index_on` so callers pricing many
+    /// carts against the same catalog only build it once. Includes cross-line `promotions` and
+    /// the lines they add, so it's what `price` would charge. Lines for items that are no longer
+    /// in the catalog aren't charged; `reprice` reports them.
+    fn total_indexed(
+        &self,
+        index: &std::collections::HashMap<&str, &Item>,
+        promotions: &[Promotion],
+        date: &chrono::NaiveDate,
+    ) -> f64 {
+        pricing::best_total(self, index, promotions, date)
+            + self
+                .promotional_lines_indexed(index, promotions, date)
+                .iter()
+                .map(|line| line.price)
+                .sum::<f64>()
+    }
+
+    /// The date sales are evaluated against: the pickup date if one is set, `date` otherwise.
+    fn pricing_date(&self, date: &chrono::NaiveDate) -> chrono::NaiveDate {
+        match &self.pickup {
+            Some(pickup) => pickup.date(),
+            None => *date,
+        }
+    }
+
+    /// What a line costs, never less than its floor price allows.
+    fn line_total(item: &Item, quantity: usize, date: &chrono::NaiveDate) -> f64 {
+        let total = Self::discounted_line_total(item, quantity, date);
+        match item.discount_floor() {
+            Some(floor_price) => total.max(floor_price * quantity as f64),
+            None => total,
+        }
+    }
+
+    fn discounted_line_total(item: &Item, quantity: usize, date: &chrono::NaiveDate) -> f64 {
+        match &item.sale {
+            Some(sale) if sale.date.matches(date) => {
+                Self::apply_sale_price(&sale.sale_price, quantity, item.price)
+            }
+            Some(_) => quantity as f64 * item.price,
+            None => match &item.bulk_pricing {
+                Some(bulk_pricing) => Self::apply_bulk_pricing(bulk_pricing, quantity, item.price),
+                None => quantity as f64 * item.price,
+            },
+        }
+    }
+
+    /// Charges whole bulk groups at the bulk price and any units left over at `price`.
+    fn apply_bulk_pricing(bulk_pricing: &BulkPricing, quantity: usize, price: f64) -> f64 {
+        let bulk_count = quantity / bulk_pricing.amount as usize;
+        let remainder = quantity % bulk_pricing.amount as usize;
+        bulk_count as f64 * bulk_pricing.total_price + remainder as f64 * price
+    }
+
+    /// How `line_total` arrived at its figure, e.g. "6 @ bulk 6-for-$6.00, 2 @ $1.25".
+    fn explain_line(item: &Item, quantity: usize, date: &chrono::NaiveDate) -> String {
+        let regular = format!("${:.2}", item.price);
+        // Units charged in groups of `size`, and what's left over at the regular price.
+        let grouped = |size: usize, description: String| {
+            let size = size.max(1);
+            vec![
+                (quantity / size * size, description),
+                (quantity % size, regular.clone()),
+            ]
+        };
+        let parts = match &item.sale {
+            Some(sale) if sale.date.matches(date) => match &sale.sale_price {
+                SalePrice::QuantityForFixedPrice(sale_quantity, sale_price) => grouped(
+                    *sale_quantity as usize,
+                    format!("sale {sale_quantity}-for-${sale_price:.2}"),
+                ),
+                SalePrice::PercentageOff(discount) => vec![(
+                    quantity,
+                    format!(
+                        "${:.2} ({}% off)",
+                        item.price * (1.0 - discount),
+                        format_percent(*discount)
+                    ),
+                )],
+                SalePrice::TwoForOne => grouped(2, "two-for-one".to_string()),
+                SalePrice::BuyOneGetOnePercentOff(discount) => grouped(
+                    2,
+                    format!("buy one get one {}% off", format_percent(*discount)),
+                ),
+                SalePrice::FixedUnitPrice(unit_price) => {
+                    vec![(quantity, format!("sale ${unit_price:.2}"))]
+                }
+            },
+            Some(_) => vec![(quantity, regular.clone())],
+            None => match &item.bulk_pricing {
+                Some(bulk_pricing) => grouped(
+                    bulk_pricing.amount as usize,
+                    format!(
+                        "bulk {}-for-${:.2}",
+                        bulk_pricing.amount, bulk_pricing.total_price
+                    ),
+                ),
+                None => vec![(quantity, regular.clone())],
+            },
+        };
+        let mut explanation = parts
+            .into_iter()
+            .filter(|(units, _)| *units > 0)
+            .map(|(units, description)| format!("{units} @ {description}"))
+            .collect::<Vec<_>>()
+            .join(", ");
+        if Self::line_total(item, quantity, date)
+            > Self::discounted_line_total(item, quantity, date)
+        {
+            let floor_price = item.discount_floor().unwrap_or_default();
+            explanation += &format!(", raised to the ${floor_price:.2} floor price");
+        }
+        explanation
+    }
+
+    /// Each line's total and how it was arrived at, in the order lines were added, so support can
+    /// answer "why was I charged this?". Cross-line promotions apply to the order as a whole and
+    /// aren't included.
+    fn breakdown(&self, catalog: &Catalog, date: &chrono::NaiveDate) -> Vec<LineBreakdown> {
+        let date = self.pricing_date(date);
+        let rescheduled = catalog.rescheduled_items(&date);
+        let index = catalog.index_on(&rescheduled);
+        self.items_sorted(&catalog.items, SortKey::AddedAt)
+            .into_iter()
+            .filter_map(|(product, quantity)| self.breakdown_line(&index, product, quantity, &date))
+            .collect()
+    }
+
+    /// One line of `breakdown`, priced on `date`, or `None` if its item is no longer in `index`.
+    fn breakdown_line(
+        &self,
+        index: &std::collections::HashMap<&str, &Item>,
+        product: &str,
+        quantity: usize,
+        date: &chrono::NaiveDate,
+    ) -> Option<LineBreakdown> {
+        let item = index.get(product)?;
+        let (total, explanation) = match self.price_overrides.get(product) {
+            Some(price_override) => (
+                price_override.price * quantity as f64,
+                format!(
+                    "{quantity} @ ${:.2} (price override: {})",
+                    price_override.price, price_override.reason
+                ),
+            ),
+            None => (
+                Self::line_total(item, quantity, date),
+                Self::explain_line(item, quantity, date),
+            ),
+        };
+        Some(LineBreakdown {
+            product: product.to_string(),
+            quantity,
+            total,
+            explanation,
+        })
+    }
+
+    /// Prices the cart against `catalog`, including its cross-line promotions, remembering which
+    /// version of it was used.
+    fn price(&mut self, catalog: &Catalog, date: &chrono::NaiveDate) -> f64 {
+        let rescheduled = catalog.rescheduled_items(&self.pricing_date(date));
+        let index = catalog.index_on(&rescheduled);
+        self.catalog_version = Some(catalog.version);
+        self.priced_at = self
+            .products
+            .keys()
+            .filter_map(|product| Some((*product, index.get(product)?.price)))
+            .collect();
+        self.total_indexed(&index, &catalog.promotions, date)
+    }
+
+    /// Lines promotions add to the order on top of what's in the cart. They're worked out afresh
+    /// each time, so a reward disappears as soon as the cart stops qualifying for it.
+    fn promotional_lines(
+        &self,
+        catalog: &Catalog,
+        date: &chrono::NaiveDate,
+    ) -> Vec<PromotionalLine> {
+        let rescheduled = catalog.rescheduled_items(&self.pricing_date(date));
+        self.promotional_lines_indexed(&catalog.index_on(&rescheduled), &catalog.promotions, date)
+    }
+
+    /// Like `promotional_lines`, but reuses an index built by `Catalog::index_on`.
+    fn promotional_lines_indexed(
+        &self,
+        index: &std::collections::HashMap<&str, &Item>,
+        promotions: &[Promotion],
+        date: &chrono::NaiveDate,
+    ) -> Vec<PromotionalLine> {
+        let date = self.pricing_date(date);
+        let mut lines = Vec::new();
+        for promotion in promotions {
+            let qualifying_lines = self.products.iter().filter_map(|(product, quantity)| {
+                let item = index.get(product)?;
+                promotion
+                    .qualifies(item.id)
+                    .then_some((*product, *item, *quantity))
+            });
+            let (reward, price, gift) = match promotion {
+                Promotion::CheapestFree { .. } => continue,
+                Promotion::SpendAndGet {
+                    threshold,
+                    reward,
+                    reward_price,
+                    ..
+                } => {
+                    let spent: f64 = qualifying_lines
+                        .map(
+                            |(product, item, quantity)| match self.price_overrides.get(product) {
+                                Some(price_override) => price_override.price * quantity as f64,
+                                None => Self::line_total(item, quantity, &date),
+                            },
+                        )
+                        .sum();
+                    if spent < *threshold {
+                        continue;
                     }
-                    _ => *quantity as f64 * item.price,
-                },
-                None => match &item.bulk_pricing {
-                    Some(bulk_pricing) if *quantity >= bulk_pricing.amount as usize => {
-                        let bulk_count = *quantity / bulk_pricing.amount as usize;
-                        let remainder = *quantity % bulk_pricing.amount as usize;
-                        bulk_count as f64 * bulk_pricing.total_price + remainder as f64 * item.price
+                    (*reward, *reward_price, false)
+                }
+                Promotion::GiftWithPurchase {
+                    min_units, gift, ..
+                } => {
+                    let units: usize = qualifying_lines.map(|(_, _, quantity)| quantity).sum();
+                    if units < *min_units as usize {
+                        continue;
                     }
-                    _ => *quantity as f64 * item.price,
-                },
+                    (*gift, 0.0, true)
+                }
+            };
+            if let Some(reward) = index.values().find(|item| item.id == reward) {
+                lines.push(PromotionalLine {
+                    item_id: reward.id,
+                    name: reward.name.clone(),
+                    price,
+                    gift,
+                });
+            }
+        }
+        lines
+    }
+
+    fn summary(&self, catalog: &Catalog, date: &chrono::NaiveDate) -> CartSummary {
+        let date = self.pricing_date(date);
+        let index = index_items(&catalog.items);
+        let items: Vec<&Item> = self
+            .products
+            .keys()
+            .filter_map(|product| index.get(product).copied())
+            .collect();
+        let sales = items
+            .iter()
+            .filter(|item| {
+                item.sale
+                    .as_ref()
+                    .is_some_and(|sale| sale.date.matches(&date))
+            })
+            .count();
+        let promotions = catalog
+            .promotions
+            .iter()
+            .filter(|promotion| items.iter().any(|item| promotion.qualifies(item.id)))
+            .count();
+        CartSummary {
+            distinct_products: self.products.len(),
+            total_units: self.products.values().sum(),
+            applicable_promotions: sales + promotions,
+        }
+    }
+
+    /// Brings the cart up to date with a changed catalog, flagging lines whose price on `date`
+    /// changed and lines whose item is gone. Those are removed when `remove_missing` is set. The
+    /// next call to `price` compares against the catalog as it is now.
+    fn reprice(
+        &mut self,
+        catalog: &Catalog,
+        date: &chrono::NaiveDate,
+        remove_missing: bool,
+    ) -> &[RepriceAdjustment] {
+        let rescheduled = catalog.rescheduled_items(&self.pricing_date(date));
+        let index = catalog.index_on(&rescheduled);
+        let mut adjustments = Vec::new();
+        let mut products: Vec<&'a str> = self.products.keys().copied().collect();
+        products.sort_unstable();
+        for product in products {
+            match (index.get(product), self.priced_at.get(product)) {
+                (None, _) if catalog.trash.iter().any(|(item, _)| item.name == product) => {
+                    adjustments.push(RepriceAdjustment::TemporarilyUnavailable {
+                        product: product.to_string(),
+                    })
+                }
+                (None, _) if remove_missing => {
+                    self.remove_line(audit::Actor::System, product);
+                    adjustments.push(RepriceAdjustment::Removed {
+                        product: product.to_string(),
+                    });
+                }
+                (None, _) => adjustments.push(RepriceAdjustment::Unavailable {
+                    product: product.to_string(),
+                }),
+                (Some(item), Some(&was)) if item.price != was => {
+                    adjustments.push(RepriceAdjustment::PriceChanged {
+                        product: product.to_string(),
+                        was,
+                        now: item.price,
+                    });
+                    self.priced_at.insert(product, item.price);
+                }
+                (Some(item), _) => {
+                    self.priced_at.insert(product, item.price);
+                }
+            }
+        }
+        self.catalog_version = Some(catalog.version);
+        self.adjustments = adjustments;
+        &self.adjustments
+    }
+
+    /// Whether the catalog has changed since the cart was last priced.
+    fn is_stale(&self, catalog: &Catalog) -> bool {
+        self.catalog_version != Some(catalog.version)
+    }
+
+    /// A copy of the cart holding only the given lines. Fails if any of them isn't in the cart.
+    fn select_lines(&self, products: &[&str]) -> std::result::Result<ShoppingCart<'a>, CartError> {
+        let mut selected = ShoppingCart::new();
+        selected.pickup = self.pickup;
+        selected.verified_age = self.verified_age;
+        selected.next_added = self.next_added;
+        for product in products {
+            let Some((product, quantity)) = self.products.get_key_value(*product) else {
+                return Err(CartError::NotInCart {
+                    product: product.to_string(),
+                });
             };
+            selected.products.insert(product, *quantity);
+            if let Some(added) = self.added.get(product) {
+                selected.added.insert(product, *added);
+            }
+            if let Some(price_override) = self.price_overrides.get(product) {
+                selected
+                    .price_overrides
+                    .insert(product, price_override.clone());
+            }
+        }
+        Ok(selected)
+    }
+
+    /// Purchases only the given lines, returning their total and leaving the rest of the cart intact.
+    /// The lines are priced together the same way as `price`, so bulk thresholds and promotions are
+    /// checked against what is actually bought. Fails if any of them is no longer sold.
+    fn checkout_lines(
+        &mut self,
+        products: &[&str],
+        catalog: &Catalog,
+        date: &chrono::NaiveDate,
+    ) -> std::result::Result<f64, CartError> {
+        let mut selected = self.select_lines(products)?;
+        selected.check_in_catalog(catalog)?;
+        selected.check_age(&catalog.items)?;
+        selected.check_availability(&catalog.items, date)?;
+        let total = selected.price(catalog, date);
+        if total > self.limits.max_order_value {
+            return Err(CartError::OrderValueExceeded {
+                total,
+                max: self.limits.max_order_value,
+            });
+        }
+        for product in selected.products.keys() {
+            self.remove_line(audit::Actor::Customer, product);
+        }
+        Ok(total)
+    }
+
+    /// Like `checkout_lines`, but paid in cash. Nothing is removed from the cart if `tendered`
+    /// doesn't cover the total rounded to the nearest nickel.
+    fn checkout_cash(
+        &mut self,
+        products: &[&str],
+        catalog: &Catalog,
+        date: &chrono::NaiveDate,
+        tendered: f64,
+    ) -> std::result::Result<cash::CashPayment, CartError> {
+        let total = self.select_lines(products)?.price(catalog, date);
+        let insufficient = |due| CartError::InsufficientTender { due, tendered };
+        cash::CashPayment::new(total, tendered).map_err(insufficient)?;
+        let total = self.checkout_lines(products, catalog, date)?;
+        cash::CashPayment::new(total, tendered).map_err(insufficient)
+    }
+
+    /// Like `checkout_lines`, but also books made-to-order items against the kitchen's capacity
+    /// for the pickup date.
+    fn checkout_lines_with_capacity(
+        &mut self,
+        products: &[&str],
+        catalog: &Catalog,
+        date: &chrono::NaiveDate,
+        capacity: &mut capacity::ProductionCapacity,
+    ) -> std::result::Result<f64, CartError> {
+        let items_by_name = index_items(&catalog.items);
+        let lines: Vec<(u32, u32)> = products
+            .iter()
+            .filter_map(|product| {
+                let item = items_by_name.get(product)?;
+                let quantity = self.products.get(product)?;
+                Some((item.id, *quantity as u32))
+            })
+            .collect();
+        let pickup_date = self.pricing_date(date);
+        let sold_out = |item_id| CartError::SoldOutForDate {
+            product: catalog
+                .items
+                .iter()
+                .find(|item| item.id == item_id)
+                .unwrap()
+                .name
+                .clone(),
+            date: pickup_date,
+        };
+        capacity.check(&lines, pickup_date).map_err(sold_out)?;
+        let total = self.checkout_lines(products, catalog, date)?;
+        capacity.reserve(&lines, pickup_date).map_err(sold_out)?;
+        Ok(total)
+    }
+
+    /// Like `checkout_lines`, but also books the order into the pickup slot its pickup time falls
+    /// in. Release the slot with `PickupSlots::release` if the order is cancelled.
+    fn checkout_lines_in_slot(
+        &mut self,
+        products: &[&str],
+        catalog: &Catalog,
+        date: &chrono::NaiveDate,
+        slots: &mut slots::PickupSlots,
+    ) -> std::result::Result<f64, CartError> {
+        let pickup = self.pickup.ok_or(CartError::PickupRequired)?;
+        if slots
+            .remaining(pickup)
+            .is_none_or(|remaining| remaining == 0)
+        {
+            return Err(CartError::SlotFull { pickup });
         }
-        total
+        let total = self.checkout_lines(products, catalog, date)?;
+        slots
+            .reserve(pickup)
+            .ok_or(CartError::SlotFull { pickup })?;
+        Ok(total)
     }
 
     fn apply_sale_price(sale_price: &SalePrice, quantity: usize, price: f64) -> f64 {
@@ -62,15 +965,41 @@ impl<'a> ShoppingCart<'a> {
                 let remainder = quantity % 2;
                 pairs as f64 * price + remainder as f64 * price
             }
+            SalePrice::BuyOneGetOnePercentOff(discount) => {
+                let pairs = quantity / 2;
+                let remainder = quantity % 2;
+                pairs as f64 * (price + price * (1.0 - discount)) + remainder as f64 * price
+            }
+            SalePrice::FixedUnitPrice(unit_price) => *unit_price * quantity as f64,
         }
     }
 
     fn clear(&mut self) {
         self.products.clear();
+        self.price_overrides.clear();
+        self.added.clear();
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// A fraction as a percentage to one decimal place, without trailing zeros, e.g. 0.29 as "29".
+fn format_percent(fraction: f64) -> String {
+    let percent = format!("{:.1}", fraction * 100.0);
+    percent
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Looks items up by name, so pricing a cart doesn't scan the whole catalog for every line.
+fn index_items(items: &[Item]) -> std::collections::HashMap<&str, &Item> {
+    items
+        .iter()
+        .map(|item| (item.name.as_str(), item))
+        .collect()
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "strict-catalog", serde(deny_unknown_fields))]
 struct Item {
     id: u32,
     name: String,
@@ -80,118 +1009,1162 @@ struct Item {
     #[serde(rename = "bulkPricing")]
     bulk_pricing: Option<BulkPricing>,
     sale: Option<Sale>,
+    /// The minimum age a customer must be verified as to buy this item.
+    #[serde(rename = "ageRestricted")]
+    age_restricted: Option<u8>,
+    #[serde(default)]
+    allergens: Vec<String>,
+    /// Passed through from the catalog as-is for display.
+    nutrition: Option<serde_json::Value>,
+    /// Seasonal items can be pre-ordered before this date, but not picked up.
+    #[serde(rename = "availableFrom")]
+    available_from: Option<chrono::NaiveDate>,
+    /// What one unit costs the bakery to make, for margin reporting.
+    cost: Option<f64>,
+    /// The least a unit may sell for, however discounts combine. It never raises a unit above its
+    /// regular price, and price overrides aren't bound by it.
+    #[serde(rename = "floorPrice")]
+    floor_price: Option<f64>,
+}
+
+impl Item {
+    /// Per-unit price for shelf labels, e.g. "$1.25/cookie, $1.00/cookie with bulk deal".
+    fn unit_price_display(&self) -> String {
+        let unit = self.name.to_lowercase();
+        let display = format!("${:.2}/{unit}", self.price);
+        match &self.bulk_pricing {
+            Some(bulk_pricing) => format!(
+                "{display}, ${:.2}/{unit} with bulk deal",
+                bulk_pricing.total_price / bulk_pricing.amount as f64
+            ),
+            None => display,
+        }
+    }
+
+    /// The floor price as it applies to discounts. It never raises a unit above its regular price.
+    fn discount_floor(&self) -> Option<f64> {
+        self.floor_price
+            .map(|floor_price| floor_price.min(self.price))
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "strict-catalog", serde(deny_unknown_fields))]
 struct BulkPricing {
     amount: u32,
     #[serde(rename = "totalPrice")]
     total_price: f64,
 }
 
+impl BulkPricing {
+    /// Rejects a bulk deal on groups of zero, which can't be priced.
+    fn validate(&self) -> std::result::Result<(), String> {
+        if self.amount == 0 {
+            return Err("bulk pricing needs groups of at least 1 item".to_string());
+        }
+        Ok(())
+    }
+}
+
 /// The sale price can be a fixed price, a percentage discount, or a two-for-one deal
 /// Dates           | Product                       | Sale Price
 /// ----------------|-------------------------------|-----------
 /// Every Friday    | 8 Cookies                     | $6.00
 /// Every October 1 | Any # of Key Lime Cheesecakes | 25% off
 /// Every Tuesday   | Mini Gingerbread Donuts       | Two for one
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 enum SalePrice {
     QuantityForFixedPrice(u32, f64),
     PercentageOff(f64),
     TwoForOne,
+    /// Every second unit is this fraction off, e.g. 0.5 for "buy one, get one half off".
+    BuyOneGetOnePercentOff(f64),
+    /// Every unit at this price, however many are bought, e.g. 25¢ donuts all day Tuesday.
+    FixedUnitPrice(f64),
 }
 /// Dates           | Product                       | Sale Price
 /// ----------------|-------------------------------|-----------
 /// Every Friday    | 8 Cookies                     | $6.00
 /// Every October 1 | Any # of Key Lime Cheesecakes | 25% off
 /// Every Tuesday   | Mini Gingerbread Donuts       | Two for one
-/// `SaleDate` can be either a month and day, or a day of the week.
-#[derive(Debug, Deserialize, Serialize)]
+/// `SaleDate` can be a month and day, the last day of every month, a day of the week, several
+/// days of the week or a whole month, or a combination of those, e.g.
+/// `All([DayOfWeek(Fri), Month(10)])` for every Friday in October.
+#[derive(Clone, Debug, Deserialize, Serialize)]
 enum SaleDate {
+    /// `MonthAndDay(2, 29)` falls on February 28 in years without a leap day.
     MonthAndDay(u32, u32),
+    LastDayOfMonth,
     DayOfWeek(Weekday),
+    /// E.g. `["Sat", "Sun"]` for a weekend special.
+    Weekdays(std::collections::HashSet<Weekday>),
+    /// Every day of a month, 1 to 12.
+    Month(u32),
+    /// Dates matching every condition.
+    All(Vec<SaleDate>),
+    /// Dates matching at least one condition.
+    Any(Vec<SaleDate>),
+}
+impl SaleDate {
+    fn matches(&self, date: &chrono::NaiveDate) -> bool {
+        match self {
+            SaleDate::MonthAndDay(2, 29) if !date.leap_year() => {
+                date.month() == 2 && date.day() == 28
+            }
+            SaleDate::MonthAndDay(month, day) => date.month() == *month && date.day() == *day,
+            SaleDate::LastDayOfMonth => date.succ_opt().is_none_or(|next| next.day() == 1),
+            SaleDate::DayOfWeek(weekday) => date.weekday() == *weekday,
+            SaleDate::Weekdays(weekdays) => weekdays.contains(&date.weekday()),
+            SaleDate::Month(month) => date.month() == *month,
+            SaleDate::All(conditions) => conditions.iter().all(|condition| condition.matches(date)),
+            SaleDate::Any(conditions) => conditions.iter().any(|condition| condition.matches(date)),
+        }
+    }
+
+    /// Whether no date can ever match, like February 30 or an empty set of weekdays.
+    /// Combinations are checked condition by condition, not for contradictions between them.
+    fn never_fires(&self) -> bool {
+        match self {
+            // 2000 was a leap year, so February 29 counts as a real date.
+            SaleDate::MonthAndDay(month, day) => {
+                chrono::NaiveDate::from_ymd_opt(2000, *month, *day).is_none()
+            }
+            SaleDate::LastDayOfMonth | SaleDate::DayOfWeek(_) => false,
+            SaleDate::Weekdays(weekdays) => weekdays.is_empty(),
+            SaleDate::Month(month) => !(1..=12).contains(month),
+            SaleDate::All(conditions) => conditions.iter().any(SaleDate::never_fires),
+            SaleDate::Any(conditions) => conditions.iter().all(SaleDate::never_fires),
+        }
+    }
 }
-#[derive(Debug, Deserialize, Serialize)]
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[cfg_attr(feature = "strict-catalog", serde(deny_unknown_fields))]
 struct Sale {
     date: SaleDate,
     sale_price: SalePrice,
 }
 
-fn parse(json_data: &str) -> Result<Vec<Item>> {
-    let data: serde_json::Value = serde_json::from_str(json_data)?;
-    let items = data["treats"].as_array().unwrap();
-    let items: Vec<Item> = serde_json::from_value(serde_json::Value::Array(items.clone()))?;
-    Ok(items)
-}
-
-fn main() -> Result<()> {
-    let json_data = r#"
+impl Sale {
+    /// Rejects sales that can't be what was meant, like one on April 31 or 150% off.
+    fn validate(&self) -> std::result::Result<(), String> {
+        if self.date.never_fires() {
+            return Err("the sale is on a date that never occurs".to_string());
+        }
+        if let SalePrice::PercentageOff(discount) | SalePrice::BuyOneGetOnePercentOff(discount) =
+            self.sale_price
         {
-          "treats": [
-            {
-              "id": 1,
-              "name": "Brownie",
-              "imageURL": "https://encrypted-tbn0.gstatic.com/images?q=tbn:ANd9GcTHdr1eTXEMs68Dx-b_mZT0RpifEQ8so6A1unRsJlyJIPe0LUE2HQ",
-              "price": 2.0,
-              "bulkPricing": {
-                "amount": 4,
-                "totalPrice": 7.0
-              }
-            },
-            {
-              "id": 2,
-              "name": "Key Lime Cheesecake",
-              "imageURL": "http://1.bp.blogspot.com/-7we9Z0C_fpI/T90JXcg3YsI/AAAAAAAABn4/EN7u2vMuRug/s1600/key+lime+cheesecake+slice+in+front.jpg",
-              "price": 8.0,
-              "bulkPricing": null
-            },
-            {
-              "id": 3,
-              "name": "Cookie",
-              "imageURL": "http://www.mayheminthekitchen.com/wp-content/uploads/2015/05/chocolate-cookie-square.jpg",
-              "price": 1.25,
-              "bulkPricing": {
-                "amount": 6,
-                "totalPrice": 6.0
-              }
-            },
-            {
-              "id": 4,
-              "name": "Mini Gingerbread Donut",
-              "imageURL": "https://i.etsystatic.com/29050134/r/il/634971/3087380231/il_794xN.3087380231_n32u.jpg",
-              "price": 0.5,
-              "bulkPricing": null
+            if !(0.0..=1.0).contains(&discount) {
+                return Err(format!(
+                    "the sale is {}% off, which isn't between 0% and 100%",
+                    format_percent(discount)
+                ));
             }
-          ]
         }
-    "#;
-    let data = parse(json_data)?;
-    println!("{:#?}", data);
+        if let SalePrice::FixedUnitPrice(unit_price) = self.sale_price {
+            if unit_price < 0.0 {
+                return Err(format!("the sale price of {unit_price} is negative"));
+            }
+        }
+        if let SalePrice::QuantityForFixedPrice(0, _) = self.sale_price {
+            return Err("the sale is on groups of 0 items".to_string());
+        }
+        Ok(())
+    }
 
-    let mut cart = ShoppingCart::new();
-    cart.add("Key Lime Cheesecake", 1);
-    println!(
-        "Total: {}",
-        cart.total(&data, &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap())
-    );
-    cart.clear();
+    /// The start and end of the run of sale days that includes `now`, or `None` if the sale isn't
+    /// on. Sales are priced by date, so they start and end at midnight. A run is cut off after a
+    /// year.
+    fn current_run(
+        &self,
+        now: chrono::NaiveDateTime,
+    ) -> Option<(chrono::NaiveDateTime, chrono::NaiveDateTime)> {
+        let today = now.date();
+        if !self.date.matches(&today) {
+            return None;
+        }
+        let first = today
+            .iter_days()
+            .rev()
+            .take(366)
+            .take_while(|date| self.date.matches(date))
+            .last()?;
+        let last = today
+            .iter_days()
+            .take(366)
+            .take_while(|date| self.date.matches(date))
+            .last()?;
+        Some((
+            first.and_time(chrono::NaiveTime::MIN),
+            last.succ_opt()?.and_time(chrono::NaiveTime::MIN),
+        ))
+    }
 
-    Ok(())
+    /// How long until the sale ends, for countdown timers.
+    fn time_remaining(&self, now: chrono::NaiveDateTime) -> Option<chrono::Duration> {
+        let (_, ends) = self.current_run(now)?;
+        Some(ends - now)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// A sale that is on right now, from `Catalog::active_sales`.
+#[derive(Debug, PartialEq)]
+struct ActiveSale {
+    item_id: u32,
+    starts: chrono::NaiveDateTime,
+    ends: chrono::NaiveDateTime,
+}
 
-    #[test]
-    fn test_parse() {
-        let json_data = r#"
-        {
-            "treats": [
-              {
-                "id": 1,
-                "name": "Brownie",
+/// The top level of a catalog file such as `products-data.json`.
+#[derive(Deserialize)]
+#[cfg_attr(feature = "strict-catalog", serde(deny_unknown_fields))]
+struct CatalogFile {
+    #[serde(deserialize_with = "deserialize_treats")]
+    treats: Vec<Item>,
+}
+
+/// Deserializes the treats one at a time, so errors point at the offending item.
+fn deserialize_treats<'de, D>(deserializer: D) -> std::result::Result<Vec<Item>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct TreatsVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for TreatsVisitor {
+        type Value = Vec<Item>;
+
+        fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            f.write_str("a list of treats")
+        }
+
+        fn visit_seq<A>(self, mut seq: A) -> std::result::Result<Vec<Item>, A::Error>
+        where
+            A: serde::de::SeqAccess<'de>,
+        {
+            use serde::de::Error;
+
+            let mut items = Vec::new();
+            loop {
+                let index = items.len();
+                let item: Item = match seq.next_element() {
+                    Ok(Some(item)) => item,
+                    Ok(None) => return Ok(items),
+                    Err(err) => return Err(A::Error::custom(format!("treats[{index}]: {err}"))),
+                };
+                media::validate_image_url(&item.image_url)
+                    .map_err(|err| A::Error::custom(format!("treats[{index}]: {err}")))?;
+                if let Some(bulk_pricing) = &item.bulk_pricing {
+                    bulk_pricing
+                        .validate()
+                        .map_err(|err| A::Error::custom(format!("treats[{index}]: {err}")))?;
+                }
+                if let Some(sale) = &item.sale {
+                    sale.validate()
+                        .map_err(|err| A::Error::custom(format!("treats[{index}]: {err}")))?;
+                }
+                items.push(item);
+            }
+        }
+    }
+
+    deserializer.deserialize_seq(TreatsVisitor)
+}
+
+fn parse(json_data: &str) -> Result<Vec<Item>> {
+    let catalog: CatalogFile = serde_json::from_str(json_data)?;
+    Ok(catalog.treats)
+}
+
+/// The menu carts are priced against. Every edit bumps `version`, so carts can tell they were
+/// priced against an older menu.
+#[derive(Debug)]
+struct Catalog {
+    items: Vec<Item>,
+    promotions: Vec<Promotion>,
+    version: u64,
+    /// Soft-deleted items and when they were deleted, until they're restored or purged.
+    trash: Vec<(Item, chrono::NaiveDateTime)>,
+    templates: std::collections::HashMap<String, ItemTemplate>,
+    /// Every price change made through `set_price` or `adjust_prices`, oldest first.
+    price_log: Vec<PriceChange>,
+    /// Price changes staged for a future date, ordered by that date.
+    scheduled_prices: Vec<ScheduledPrice>,
+    /// Who made each edit, oldest first.
+    audit_log: audit::AuditLog,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+struct ScheduledPrice {
+    item_id: u32,
+    price: f64,
+    /// The first day the new price applies.
+    effective: chrono::NaiveDate,
+}
+
+/// A change applied to many prices at once by `Catalog::adjust_prices`.
+#[derive(Clone, Copy, Debug)]
+enum PriceAdjustment {
+    /// A fraction of the current price, e.g. 0.03 for a 3% increase. Negative to lower prices.
+    Percentage(f64),
+    /// An amount added to the current price. Negative to lower prices.
+    Fixed(f64),
+}
+
+impl PriceAdjustment {
+    /// The adjusted price, rounded to the nearest multiple of `round_to` and never below zero.
+    fn apply(self, price: f64, round_to: f64) -> f64 {
+        let adjusted = match self {
+            PriceAdjustment::Percentage(fraction) => price * (1.0 + fraction),
+            PriceAdjustment::Fixed(amount) => price + amount,
+        };
+        let rounded = if round_to > 0.0 {
+            (adjusted / round_to).round() * round_to
+        } else {
+            adjusted
+        };
+        ((rounded * 100.0).round() / 100.0).max(0.0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct PriceChange {
+    item_id: u32,
+    was: f64,
+    now: f64,
+}
+
+/// Defaults shared by a family of items, like every cookie flavour, for `Catalog::add_from_template`.
+#[derive(Clone, Debug, Serialize)]
+struct ItemTemplate {
+    price: f64,
+    bulk_pricing: Option<BulkPricing>,
+    sale: Option<Sale>,
+    age_restricted: Option<u8>,
+    allergens: Vec<String>,
+}
+
+impl From<&Item> for ItemTemplate {
+    fn from(item: &Item) -> Self {
+        Self {
+            price: item.price,
+            bulk_pricing: item.bulk_pricing.clone(),
+            sale: item.sale.clone(),
+            age_restricted: item.age_restricted,
+            allergens: item.allergens.clone(),
+        }
+    }
+}
+
+/// Deals that span several cart lines, applied by `ShoppingCart::price`.
+#[derive(Debug, Serialize)]
+enum Promotion {
+    /// Every `buy` units of the qualifying items, by ID, get the cheapest of them free, e.g.
+    /// "3 for 2 across the pastry case". Units are grouped from the most expensive down, and a
+    /// unit counted toward this can't also count toward a bulk deal or sale.
+    CheapestFree { qualifying: Vec<u32>, buy: u32 },
+    /// Spending at least `threshold` on the qualifying items, by ID, adds one `reward` item to the
+    /// order at `reward_price`, e.g. "spend $25 on pastries, get a free coffee". Spend is counted
+    /// after sales and bulk deals but before other promotions.
+    SpendAndGet {
+        qualifying: Vec<u32>,
+        threshold: f64,
+        reward: u32,
+        reward_price: f64,
+    },
+    /// Buying at least `min_units` of the qualifying items, by ID, adds one `gift` item to the
+    /// order for free.
+    GiftWithPurchase {
+        qualifying: Vec<u32>,
+        min_units: u32,
+        gift: u32,
+    },
+}
+
+/// A line a promotion adds to the order, priced separately from the cart's own lines. No other
+/// discount applies to it.
+#[derive(Clone, Debug, PartialEq)]
+struct PromotionalLine {
+    item_id: u32,
+    name: String,
+    price: f64,
+    /// Whether this is a free gift, to be shown as such on the order.
+    gift: bool,
+}
+
+impl Promotion {
+    fn qualifies(&self, item_id: u32) -> bool {
+        match self {
+            Promotion::CheapestFree { qualifying, .. }
+            | Promotion::SpendAndGet { qualifying, .. }
+            | Promotion::GiftWithPurchase { qualifying, .. } => qualifying.contains(&item_id),
+        }
+    }
+
+    /// Whether units of the item can be counted toward this promotion instead of their own line.
+    fn pools(&self, item_id: u32) -> bool {
+        match self {
+            Promotion::CheapestFree { .. } => self.qualifies(item_id),
+            Promotion::SpendAndGet { .. } | Promotion::GiftWithPurchase { .. } => false,
+        }
+    }
+
+    /// What the units counted toward this promotion cost together, given each unit's price and
+    /// floor price. A free unit still costs its floor price.
+    fn pool_total(&self, units: &mut [(f64, f64)]) -> f64 {
+        match self {
+            Promotion::CheapestFree { buy, .. } => {
+                let total = units.iter().map(|(price, _)| price).sum::<f64>();
+                if *buy == 0 {
+                    return total;
+                }
+                units.sort_by(|a, b| b.0.total_cmp(&a.0));
+                total
+                    - units
+                        .chunks_exact(*buy as usize)
+                        .map(|group| {
+                            let (price, floor_price) = group[group.len() - 1];
+                            (price - floor_price).max(0.0)
+                        })
+                        .sum::<f64>()
+            }
+            Promotion::SpendAndGet { .. } | Promotion::GiftWithPurchase { .. } => {
+                units.iter().map(|(price, _)| price).sum()
+            }
+        }
+    }
+
+    /// A rough per-unit cost of a unit counted toward this promotion, for when trying every
+    /// combination would take too long.
+    fn estimated_unit_price(&self, price: f64) -> f64 {
+        match self {
+            Promotion::CheapestFree { buy, .. } if *buy > 0 => {
+                price * (*buy - 1) as f64 / *buy as f64
+            }
+            Promotion::CheapestFree { .. }
+            | Promotion::SpendAndGet { .. }
+            | Promotion::GiftWithPurchase { .. } => price,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum CatalogError {
+    UnknownItem(u32),
+    /// An item can't be restored while another item has its ID.
+    DuplicateItem(u32),
+    /// Carts refer to items by name, so names must be unique.
+    DuplicateName(String),
+    UnknownTemplate(String),
+    NoScheduledPrice {
+        item_id: u32,
+        effective: chrono::NaiveDate,
+    },
+    PermissionDenied(access::PermissionDenied),
+}
+
+impl std::fmt::Display for CatalogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CatalogError::UnknownItem(id) => write!(f, "there is no item with ID {id}"),
+            CatalogError::DuplicateItem(id) => write!(f, "there is already an item with ID {id}"),
+            CatalogError::DuplicateName(name) => write!(f, "there is already an item named {name}"),
+            CatalogError::UnknownTemplate(name) => write!(f, "there is no template named {name}"),
+            CatalogError::NoScheduledPrice { item_id, effective } => write!(
+                f,
+                "there is no price change scheduled for item {item_id} on {effective}"
+            ),
+            CatalogError::PermissionDenied(denied) => denied.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for CatalogError {}
+
+impl From<access::PermissionDenied> for CatalogError {
+    fn from(denied: access::PermissionDenied) -> Self {
+        CatalogError::PermissionDenied(denied)
+    }
+}
+
+/// Suspicious sale configurations, reported by `Catalog::lint_sales`.
+#[derive(Debug, PartialEq)]
+enum SaleWarning {
+    /// The sale is on a date that doesn't exist, like February 30.
+    NeverFires { item_id: u32 },
+    /// The sale price is no better than the regular price.
+    NoDiscount { item_id: u32 },
+    /// The sale gives the item away for free.
+    GivesItemAway { item_id: u32 },
+    /// The item also has bulk pricing, and the two don't combine.
+    OverlapsBulkPricing { item_id: u32 },
+    /// The sale sells the item for less than it costs to make.
+    BelowCost { item_id: u32 },
+}
+
+impl Catalog {
+    fn new(items: Vec<Item>) -> Self {
+        Self {
+            items,
+            promotions: Vec::new(),
+            version: 1,
+            trash: Vec::new(),
+            templates: std::collections::HashMap::new(),
+            price_log: Vec::new(),
+            scheduled_prices: Vec::new(),
+            audit_log: audit::AuditLog::default(),
+        }
+    }
+
+    fn add_promotion(
+        &mut self,
+        role: access::Role,
+        promotion: Promotion,
+    ) -> std::result::Result<(), CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "add_promotion",
+            format!("promotion {}", self.promotions.len()),
+            None,
+            audit::snapshot(&promotion),
+        );
+        self.promotions.push(promotion);
+        self.version += 1;
+        Ok(())
+    }
+
+    /// Adds `item`, or replaces the item with the same ID. Fails if another item, in the catalog or
+    /// the trash, already has its name.
+    fn upsert_item(
+        &mut self,
+        role: access::Role,
+        item: Item,
+    ) -> std::result::Result<(), CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        self.check_name_free(&item.name, Some(item.id))?;
+        let after = audit::snapshot(&item);
+        let subject = format!("item {}", item.id);
+        let before = match self
+            .items
+            .iter_mut()
+            .find(|existing| existing.id == item.id)
+        {
+            Some(existing) => audit::snapshot(&std::mem::replace(existing, item)),
+            None => {
+                self.items.push(item);
+                None
+            }
+        };
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "upsert_item",
+            subject,
+            before,
+            after,
+        );
+        self.version += 1;
+        Ok(())
+    }
+
+    /// Fails if an item other than `id`, in the catalog or the trash, is named `name`. Names must be
+    /// unique because carts refer to items by name.
+    fn check_name_free(
+        &self,
+        name: &str,
+        id: Option<u32>,
+    ) -> std::result::Result<(), CatalogError> {
+        if self
+            .items
+            .iter()
+            .chain(self.trash.iter().map(|(item, _)| item))
+            .any(|item| item.name == name && Some(item.id) != id)
+        {
+            return Err(CatalogError::DuplicateName(name.to_string()));
+        }
+        Ok(())
+    }
+
+    fn remove_item(
+        &mut self,
+        role: access::Role,
+        id: u32,
+    ) -> std::result::Result<Item, CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        let item = self.take_item(id)?;
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "remove_item",
+            format!("item {id}"),
+            audit::snapshot(&item),
+            None,
+        );
+        Ok(item)
+    }
+
+    /// Takes an item out of the catalog without recording it, for callers that record their own
+    /// entry.
+    fn take_item(&mut self, id: u32) -> std::result::Result<Item, CatalogError> {
+        let index = self
+            .items
+            .iter()
+            .position(|item| item.id == id)
+            .ok_or(CatalogError::UnknownItem(id))?;
+        self.version += 1;
+        Ok(self.items.remove(index))
+    }
+
+    /// One more than the highest ID in use, counting the trash so restored items can't collide.
+    fn next_id(&self) -> u32 {
+        self.items
+            .iter()
+            .chain(self.trash.iter().map(|(item, _)| item))
+            .map(|item| item.id)
+            .max()
+            .map_or(1, |id| id + 1)
+    }
+
+    /// Adds `item` under a new ID, returning the ID.
+    fn add_item(
+        &mut self,
+        role: access::Role,
+        item: Item,
+    ) -> std::result::Result<u32, CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        self.check_name_free(&item.name, None)?;
+        let id = self.next_id();
+        let item = Item { id, ..item };
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "add_item",
+            format!("item {id}"),
+            None,
+            audit::snapshot(&item),
+        );
+        self.items.push(item);
+        self.version += 1;
+        Ok(id)
+    }
+
+    /// Copies an item, sale and bulk pricing included, under a new ID and name.
+    fn duplicate_item(
+        &mut self,
+        role: access::Role,
+        id: u32,
+        name: &str,
+    ) -> std::result::Result<u32, CatalogError> {
+        let item = self
+            .items
+            .iter()
+            .find(|item| item.id == id)
+            .ok_or(CatalogError::UnknownItem(id))?;
+        self.add_item(
+            role,
+            Item {
+                name: name.to_string(),
+                ..item.clone()
+            },
+        )
+    }
+
+    fn add_template(
+        &mut self,
+        role: access::Role,
+        name: &str,
+        template: ItemTemplate,
+    ) -> std::result::Result<(), CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        let after = audit::snapshot(&template);
+        let before = self.templates.insert(name.to_string(), template);
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "add_template",
+            format!("template {name}"),
+            before.as_ref().and_then(audit::snapshot),
+            after,
+        );
+        Ok(())
+    }
+
+    /// Adds an item with the template's pricing, returning its new ID.
+    fn add_from_template(
+        &mut self,
+        role: access::Role,
+        template: &str,
+        name: &str,
+        image_url: &str,
+    ) -> std::result::Result<u32, CatalogError> {
+        let template = self
+            .templates
+            .get(template)
+            .ok_or_else(|| CatalogError::UnknownTemplate(template.to_string()))?
+            .clone();
+        self.add_item(
+            role,
+            Item {
+                id: 0,
+                name: name.to_string(),
+                image_url: image_url.to_string(),
+                price: template.price,
+                bulk_pricing: template.bulk_pricing,
+                sale: template.sale,
+                age_restricted: template.age_restricted,
+                allergens: template.allergens,
+                nutrition: None,
+                available_from: None,
+                cost: None,
+                floor_price: None,
+            },
+        )
+    }
+
+    /// Moves an item to the trash, where `restore_item` can bring it back until it's purged.
+    fn soft_delete_item(
+        &mut self,
+        role: access::Role,
+        id: u32,
+        now: chrono::NaiveDateTime,
+    ) -> std::result::Result<(), CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        let item = self.take_item(id)?;
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "soft_delete_item",
+            format!("item {id}"),
+            audit::snapshot(&item),
+            None,
+        );
+        self.trash.push((item, now));
+        Ok(())
+    }
+
+    fn restore_item(
+        &mut self,
+        role: access::Role,
+        id: u32,
+    ) -> std::result::Result<(), CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        let index = self
+            .trash
+            .iter()
+            .position(|(item, _)| item.id == id)
+            .ok_or(CatalogError::UnknownItem(id))?;
+        if self.items.iter().any(|item| item.id == id) {
+            return Err(CatalogError::DuplicateItem(id));
+        }
+        self.check_name_free(&self.trash[index].0.name, Some(id))?;
+        let (item, _) = self.trash.remove(index);
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "restore_item",
+            format!("item {id}"),
+            None,
+            audit::snapshot(&item),
+        );
+        self.items.push(item);
+        self.version += 1;
+        Ok(())
+    }
+
+    /// Permanently removes items that have been in the trash for at least `retention_days`. This is
+    /// a scheduled job rather than a staff edit, so it isn't gated by role.
+    fn purge_trash(&mut self, now: chrono::NaiveDateTime, retention_days: u64) -> Vec<Item> {
+        let cutoff = now - chrono::Days::new(retention_days);
+        let (purged, kept) = std::mem::take(&mut self.trash)
+            .into_iter()
+            .partition(|(_, deleted_at)| *deleted_at <= cutoff);
+        self.trash = kept;
+        let purged: Vec<Item> = purged.into_iter().map(|(item, _)| item).collect();
+        for item in &purged {
+            self.audit_log.record(
+                audit::Actor::System,
+                "purge_trash",
+                format!("item {}", item.id),
+                audit::snapshot(item),
+                None,
+            );
+        }
+        if !purged.is_empty() {
+            self.version += 1;
+        }
+        purged
+    }
+
+    fn set_price(
+        &mut self,
+        role: access::Role,
+        id: u32,
+        price: f64,
+    ) -> std::result::Result<(), CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        self.change_price(audit::Actor::Staff(role), id, price)
+    }
+
+    /// `set_price` without the permission check, for scheduled jobs.
+    fn change_price(
+        &mut self,
+        actor: audit::Actor,
+        id: u32,
+        price: f64,
+    ) -> std::result::Result<(), CatalogError> {
+        let item = self.item_mut(id)?;
+        let was = std::mem::replace(&mut item.price, price);
+        self.audit_log.record(
+            actor,
+            "set_price",
+            format!("item {id}"),
+            audit::snapshot(&was),
+            audit::snapshot(&price),
+        );
+        self.price_log.push(PriceChange {
+            item_id: id,
+            was,
+            now: price,
+        });
+        self.version += 1;
+        Ok(())
+    }
+
+    /// Stages a price change for `effective`, replacing any already staged for that item and day.
+    fn schedule_price(
+        &mut self,
+        role: access::Role,
+        id: u32,
+        price: f64,
+        effective: chrono::NaiveDate,
+    ) -> std::result::Result<(), CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        self.item_mut(id)?;
+        let replaced = self
+            .scheduled_prices
+            .iter()
+            .position(|scheduled| scheduled.item_id == id && scheduled.effective == effective)
+            .map(|index| self.scheduled_prices.remove(index));
+        let scheduled = ScheduledPrice {
+            item_id: id,
+            price,
+            effective,
+        };
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "schedule_price",
+            format!("item {id}"),
+            replaced.as_ref().and_then(audit::snapshot),
+            audit::snapshot(&scheduled),
+        );
+        self.scheduled_prices.push(scheduled);
+        self.scheduled_prices
+            .sort_by_key(|scheduled| scheduled.effective);
+        self.version += 1;
+        Ok(())
+    }
+
+    fn cancel_scheduled_price(
+        &mut self,
+        role: access::Role,
+        id: u32,
+        effective: chrono::NaiveDate,
+    ) -> std::result::Result<ScheduledPrice, CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        let index = self
+            .scheduled_prices
+            .iter()
+            .position(|scheduled| scheduled.item_id == id && scheduled.effective == effective)
+            .ok_or(CatalogError::NoScheduledPrice {
+                item_id: id,
+                effective,
+            })?;
+        let cancelled = self.scheduled_prices.remove(index);
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "cancel_scheduled_price",
+            format!("item {id}"),
+            audit::snapshot(&cancelled),
+            None,
+        );
+        self.version += 1;
+        Ok(cancelled)
+    }
+
+    /// The price each item with a change scheduled by `date` will have then, by item ID.
+    fn prices_on(&self, date: &chrono::NaiveDate) -> std::collections::HashMap<u32, f64> {
+        // Scheduled prices are kept in date order, so the latest change for an item wins.
+        self.scheduled_prices
+            .iter()
+            .take_while(|scheduled| scheduled.effective <= *date)
+            .map(|scheduled| (scheduled.item_id, scheduled.price))
+            .collect()
+    }
+
+    /// Copies of just the items whose price will have changed by `date`, priced as they will be
+    /// then, for `index_on` to lay over the rest.
+    fn rescheduled_items(&self, date: &chrono::NaiveDate) -> Vec<Item> {
+        let prices = self.prices_on(date);
+        if prices.is_empty() {
+            return Vec::new();
+        }
+        self.items
+            .iter()
+            .filter_map(|item| {
+                Some(Item {
+                    price: *prices.get(&item.id)?,
+                    ..item.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// Looks items up by name as they're priced with `rescheduled`, from `rescheduled_items`, in
+    /// effect.
+    fn index_on<'c>(
+        &'c self,
+        rescheduled: &'c [Item],
+    ) -> std::collections::HashMap<&'c str, &'c Item> {
+        let mut index = index_items(&self.items);
+        index.extend(index_items(rescheduled));
+        index
+    }
+
+    /// Makes every scheduled change that has taken effect by `today` permanent. Like `purge_trash`,
+    /// this is a scheduled job, so it isn't gated by role.
+    fn apply_scheduled_prices(&mut self, today: &chrono::NaiveDate) {
+        let due: Vec<ScheduledPrice> = self
+            .scheduled_prices
+            .iter()
+            .take_while(|scheduled| scheduled.effective <= *today)
+            .cloned()
+            .collect();
+        self.scheduled_prices.drain(..due.len());
+        for scheduled in due {
+            // The item may have been deleted since the change was scheduled.
+            let _ = self.change_price(audit::Actor::System, scheduled.item_id, scheduled.price);
+        }
+    }
+
+    /// The prices `adjust_prices` would change, without changing them.
+    fn preview_price_adjustment(
+        &self,
+        filter: impl Fn(&Item) -> bool,
+        adjustment: PriceAdjustment,
+        round_to: f64,
+    ) -> Vec<PriceChange> {
+        self.items
+            .iter()
+            .filter(|item| filter(item))
+            .map(|item| PriceChange {
+                item_id: item.id,
+                was: item.price,
+                now: adjustment.apply(item.price, round_to),
+            })
+            .filter(|change| change.now != change.was)
+            .collect()
+    }
+
+    /// Adjusts the price of every item matching `filter`, e.g. "raise everything 3%, rounded to
+    /// the nearest $0.05", and returns what changed.
+    fn adjust_prices(
+        &mut self,
+        role: access::Role,
+        filter: impl Fn(&Item) -> bool,
+        adjustment: PriceAdjustment,
+        round_to: f64,
+    ) -> std::result::Result<Vec<PriceChange>, CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        let changes = self.preview_price_adjustment(filter, adjustment, round_to);
+        for change in &changes {
+            if let Some(item) = self.items.iter_mut().find(|item| item.id == change.item_id) {
+                item.price = change.now;
+            }
+            self.audit_log.record(
+                audit::Actor::Staff(role),
+                "adjust_prices",
+                format!("item {}", change.item_id),
+                audit::snapshot(&change.was),
+                audit::snapshot(&change.now),
+            );
+        }
+        if !changes.is_empty() {
+            self.price_log.extend(changes.iter().cloned());
+            self.version += 1;
+        }
+        Ok(changes)
+    }
+
+    fn add_sale(
+        &mut self,
+        role: access::Role,
+        id: u32,
+        sale: Sale,
+    ) -> std::result::Result<(), CatalogError> {
+        role.authorize(access::Permission::EditCatalog)?;
+        let after = audit::snapshot(&sale);
+        let before = self.item_mut(id)?.sale.replace(sale);
+        self.audit_log.record(
+            audit::Actor::Staff(role),
+            "add_sale",
+            format!("item {id}"),
+            before.as_ref().and_then(audit::snapshot),
+            after,
+        );
+        self.version += 1;
+        Ok(())
+    }
+
+    fn item_mut(&mut self, id: u32) -> std::result::Result<&mut Item, CatalogError> {
+        self.items
+            .iter_mut()
+            .find(|item| item.id == id)
+            .ok_or(CatalogError::UnknownItem(id))
+    }
+
+    fn lint_sales(&self) -> Vec<SaleWarning> {
+        let mut warnings = Vec::new();
+        for item in &self.items {
+            let Some(sale) = &item.sale else {
+                continue;
+            };
+            let item_id = item.id;
+            if sale.date.never_fires() {
+                warnings.push(SaleWarning::NeverFires { item_id });
+            }
+            match sale.sale_price {
+                SalePrice::QuantityForFixedPrice(quantity, price) => {
+                    if price <= 0.0 {
+                        warnings.push(SaleWarning::GivesItemAway { item_id });
+                    } else if price >= quantity as f64 * item.price {
+                        warnings.push(SaleWarning::NoDiscount { item_id });
+                    }
+                }
+                SalePrice::PercentageOff(discount) => {
+                    if discount >= 1.0 {
+                        warnings.push(SaleWarning::GivesItemAway { item_id });
+                    } else if discount <= 0.0 {
+                        warnings.push(SaleWarning::NoDiscount { item_id });
+                    }
+                }
+                SalePrice::BuyOneGetOnePercentOff(discount) => {
+                    if discount <= 0.0 {
+                        warnings.push(SaleWarning::NoDiscount { item_id });
+                    }
+                }
+                SalePrice::FixedUnitPrice(unit_price) => {
+                    if unit_price <= 0.0 {
+                        warnings.push(SaleWarning::GivesItemAway { item_id });
+                    } else if unit_price >= item.price {
+                        warnings.push(SaleWarning::NoDiscount { item_id });
+                    }
+                }
+                SalePrice::TwoForOne => {}
+            }
+            let units = match sale.sale_price {
+                SalePrice::QuantityForFixedPrice(quantity, _) => quantity.max(1) as usize,
+                _ => 2,
+            };
+            let unit_price =
+                ShoppingCart::apply_sale_price(&sale.sale_price, units, item.price) / units as f64;
+            if item.cost.is_some_and(|cost| unit_price < cost) {
+                warnings.push(SaleWarning::BelowCost { item_id });
+            }
+            if item.bulk_pricing.is_some() {
+                warnings.push(SaleWarning::OverlapsBulkPricing { item_id });
+            }
+        }
+        warnings
+    }
+
+    fn active_sales(&self, now: chrono::NaiveDateTime) -> Vec<ActiveSale> {
+        self.items
+            .iter()
+            .filter_map(|item| {
+                let (starts, ends) = item.sale.as_ref()?.current_run(now)?;
+                Some(ActiveSale {
+                    item_id: item.id,
+                    starts,
+                    ends,
+                })
+            })
+            .collect()
+    }
+
+    /// Writes the catalog in the same format `parse` reads.
+    fn save(&self, path: &std::path::Path) -> std::io::Result<()> {
+        let file = std::io::BufWriter::new(std::fs::File::create(path)?);
+        serde_json::to_writer_pretty(file, &serde_json::json!({ "treats": self.items }))?;
+        Ok(())
+    }
+}
+
+fn main() -> std::result::Result<(), Box<dyn std::error::Error>> {
+    let json_data = r#"
+        {
+          "treats": [
+            {
+              "id": 1,
+              "name": "Brownie",
+              "imageURL": "https://encrypted-tbn0.gstatic.com/images?q=tbn:ANd9GcTHdr1eTXEMs68Dx-b_mZT0RpifEQ8so6A1unRsJlyJIPe0LUE2HQ",
+              "price": 2.0,
+              "bulkPricing": {
+                "amount": 4,
+                "totalPrice": 7.0
+              }
+            },
+            {
+              "id": 2,
+              "name": "Key Lime Cheesecake",
+              "imageURL": "http://1.bp.blogspot.com/-7we9Z0C_fpI/T90JXcg3YsI/AAAAAAAABn4/EN7u2vMuRug/s1600/key+lime+cheesecake+slice+in+front.jpg",
+              "price": 8.0,
+              "cost": 3.2,
+              "bulkPricing": null
+            },
+            {
+              "id": 3,
+              "name": "Cookie",
+              "imageURL": "http://www.mayheminthekitchen.com/wp-content/uploads/2015/05/chocolate-cookie-square.jpg",
+              "price": 1.25,
+              "bulkPricing": {
+                "amount": 6,
+                "totalPrice": 6.0
+              }
+            },
+            {
+              "id": 4,
+              "name": "Mini Gingerbread Donut",
+              "imageURL": "https://i.etsystatic.com/29050134/r/il/634971/3087380231/il_794xN.3087380231_n32u.jpg",
+              "price": 0.5,
+              "bulkPricing": null
+            }
+          ]
+        }
+    "#;
+    let data = parse(json_data)?;
+    println!("{:#?}", data);
+
+    let catalog = Catalog::new(data);
+    let dummy_date = chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+    let mut cart = ShoppingCart::new();
+    cart.add("Key Lime Cheesecake", 1)?;
+    cart.add("Cookie", 7)?;
+    for line in cart.breakdown(&catalog, &dummy_date) {
+        println!("{}: {}", line.product, line.explanation);
+    }
+    println!("Total: {}", cart.price(&catalog, &dummy_date));
+    cart.clear();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{CatalogBuilder, ItemBuilder};
+
+    #[test]
+    fn test_parse() {
+        let json_data = r#"
+        {
+            "treats": [
+              {
+                "id": 1,
+                "name": "Brownie",
                 "imageURL": "https://encrypted-tbn0.gstatic.com/images?q=tbn:ANd9GcTHdr1eTXEMs68Dx-b_mZT0RpifEQ8so6A1unRsJlyJIPe0LUE2HQ",
                 "price": 2.0,
                 "bulkPricing": {
@@ -259,7 +2232,7 @@ mod tests {
 
     #[test]
     fn test_shopping_cart_total() {
-        let data = vec![
+        let catalog = Catalog::new(vec![
             Item {
               id: 1,
               name: "Brownie".to_string(),
@@ -269,6 +2242,12 @@ mod tests {
                   amount: 4,
                   total_price: 7.0,
               }),
+              age_restricted: None,
+              allergens: vec![],
+              nutrition: None,
+              available_from: None,
+              cost: None,
+              floor_price: None,
               sale: None,
             },
             Item {
@@ -277,6 +2256,12 @@ mod tests {
               image_url: "http://1.bp.blogspot.com/-7we9Z0C_fpI/T90JXcg3YsI/AAAAAAAABn4/EN7u2vMuRug/s1600/key+lime+cheesecake+slice+in+front.jpg".to_string(),
               price: 8.0,
               bulk_pricing: None,
+              age_restricted: None,
+              allergens: vec![],
+              nutrition: None,
+              available_from: None,
+              cost: None,
+              floor_price: None,
               sale: None
             },
             Item {
@@ -288,6 +2273,12 @@ mod tests {
                   amount: 6,
                   total_price: 6.0,
               }),
+              age_restricted: None,
+              allergens: vec![],
+              nutrition: None,
+              available_from: None,
+              cost: None,
+              floor_price: None,
               sale: None
             },
             Item {
@@ -296,50 +2287,172 @@ mod tests {
               image_url: "https://i.etsystatic.com/29050134/r/il/634971/3087380231/il_794xN.3087380231_n32u.jpg".to_string(),
               price: 0.5,
               bulk_pricing: None,
+              age_restricted: None,
+              allergens: vec![],
+              nutrition: None,
+              available_from: None,
+              cost: None,
+              floor_price: None,
               sale: None
             },
-        ];
+        ]);
 
         let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
 
         let mut cart = ShoppingCart::new();
-        cart.add("Cookie", 7);
-        assert_eq!(cart.total(&data, dummy_date), 7.25);
+        cart.add("Cookie", 7).unwrap();
+        assert_eq!(cart.total(&catalog, dummy_date), 7.25);
 
         cart.clear();
-        cart.add("Cookie", 1);
-        cart.add("Brownie", 4);
-        cart.add("Key Lime Cheesecake", 1);
-        assert_eq!(cart.total(&data, dummy_date), 16.25);
+        cart.add("Cookie", 1).unwrap();
+        cart.add("Brownie", 4).unwrap();
+        cart.add("Key Lime Cheesecake", 1).unwrap();
+        assert_eq!(cart.total(&catalog, dummy_date), 16.25);
 
         cart.clear();
-        cart.add("Cookie", 8);
-        assert_eq!(cart.total(&data, dummy_date), 8.50);
+        cart.add("Cookie", 8).unwrap();
+        assert_eq!(cart.total(&catalog, dummy_date), 8.50);
 
         cart.clear();
-        cart.add("Cookie", 1);
-        cart.add("Brownie", 1);
-        cart.add("Key Lime Cheesecake", 1);
-        cart.add("Mini Gingerbread Donut", 2);
-        assert_eq!(cart.total(&data, dummy_date), 12.25);
+        cart.add("Cookie", 1).unwrap();
+        cart.add("Brownie", 1).unwrap();
+        cart.add("Key Lime Cheesecake", 1).unwrap();
+        cart.add("Mini Gingerbread Donut", 2).unwrap();
+        assert_eq!(cart.total(&catalog, dummy_date), 12.25);
 
         cart.clear();
-        assert_eq!(cart.total(&data, dummy_date), 0.0);
+        assert_eq!(cart.total(&catalog, dummy_date), 0.0);
     }
 
     #[test]
-    fn test_sales() {
-        let data = vec![
-          Item {
-            id: 2,
-            name: "Key Lime Cheesecake".to_string(),
-            image_url: "http://1.bp.blogspot.com/-7we9Z0C_fpI/T90JXcg3YsI/AAAAAAAABn4/EN7u2vMuRug/s1600/key+lime+cheesecake+slice+in+front.jpg".to_string(),
-            price: 8.0,
-            bulk_pricing: None,
-            sale: Some(Sale {
-              date: SaleDate::MonthAndDay(10, 1),
-                sale_price: SalePrice::PercentageOff(0.25)
-            }),
+    fn test_checkout_cash() {
+        let catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 3).unwrap();
+        cart.add("Brownie", 1).unwrap();
+        assert_eq!(
+            cart.checkout_cash(&["Cookie"], &catalog, dummy_date, 3.0),
+            Err(CartError::InsufficientTender {
+                due: 3.75,
+                tendered: 3.0,
+            })
+        );
+        assert_eq!(cart.total(&catalog, dummy_date), 5.75);
+
+        cart.set_line_price_override(access::Role::Manager, "Brownie", 1.98, "day old")
+            .unwrap();
+        assert_eq!(
+            cart.checkout_cash(&["Brownie"], &catalog, dummy_date, 5.0),
+            Ok(cash::CashPayment {
+                total: 1.98,
+                due: 2.0,
+                tendered: 5.0,
+                change: 3.0,
+            })
+        );
+        assert_eq!(cart.total(&catalog, dummy_date), 3.75);
+    }
+
+    #[test]
+    fn test_checkout_lines_with_capacity() {
+        let catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+        let friday = chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let mut capacity = capacity::ProductionCapacity::default();
+        capacity.set_daily_limit(2, 5);
+
+        let mut cart = ShoppingCart::new();
+        cart.set_pickup(friday.and_hms_opt(9, 0, 0).unwrap());
+        cart.add("Key Lime Cheesecake", 4).unwrap();
+        assert_eq!(
+            cart.checkout_lines_with_capacity(
+                &["Key Lime Cheesecake"],
+                &catalog,
+                dummy_date,
+                &mut capacity
+            ),
+            Ok(32.0)
+        );
+        assert_eq!(capacity.remaining(2, friday), Some(1));
+
+        cart.add("Key Lime Cheesecake", 2).unwrap();
+        assert_eq!(
+            cart.checkout_lines_with_capacity(
+                &["Key Lime Cheesecake"],
+                &catalog,
+                dummy_date,
+                &mut capacity
+            ),
+            Err(CartError::SoldOutForDate {
+                product: "Key Lime Cheesecake".to_string(),
+                date: friday,
+            })
+        );
+        assert_eq!(capacity.remaining(2, friday), Some(1));
+        assert_eq!(cart.total(&catalog, dummy_date), 16.0);
+    }
+
+    #[test]
+    fn test_checkout_lines_in_slot() {
+        let catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+        let friday = chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let mut slots = slots::PickupSlots::default();
+        slots.add_slot(
+            friday.and_hms_opt(9, 0, 0).unwrap(),
+            friday.and_hms_opt(10, 0, 0).unwrap(),
+            1,
+        );
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Brownie", 1).unwrap();
+        cart.add("Cookie", 1).unwrap();
+        assert_eq!(
+            cart.checkout_lines_in_slot(&["Brownie"], &catalog, dummy_date, &mut slots),
+            Err(CartError::PickupRequired)
+        );
+
+        let pickup = friday.and_hms_opt(9, 30, 0).unwrap();
+        cart.set_pickup(pickup);
+        assert_eq!(
+            cart.checkout_lines_in_slot(&["Brownie"], &catalog, dummy_date, &mut slots),
+            Ok(2.0)
+        );
+        assert_eq!(slots.remaining(pickup), Some(0));
+        assert_eq!(
+            cart.checkout_lines_in_slot(&["Cookie"], &catalog, dummy_date, &mut slots),
+            Err(CartError::SlotFull { pickup })
+        );
+        assert_eq!(cart.total(&catalog, dummy_date), 1.25);
+
+        slots.release(pickup);
+        assert_eq!(
+            cart.checkout_lines_in_slot(&["Cookie"], &catalog, dummy_date, &mut slots),
+            Ok(1.25)
+        );
+    }
+
+    #[test]
+    fn test_sales() {
+        let catalog = Catalog::new(vec![
+          Item {
+            id: 2,
+            name: "Key Lime Cheesecake".to_string(),
+            image_url: "http://1.bp.blogspot.com/-7we9Z0C_fpI/T90JXcg3YsI/AAAAAAAABn4/EN7u2vMuRug/s1600/key+lime+cheesecake+slice+in+front.jpg".to_string(),
+            price: 8.0,
+            bulk_pricing: None,
+            age_restricted: None,
+            allergens: vec![],
+            nutrition: None,
+            available_from: None,
+            cost: None,
+            floor_price: None,
+            sale: Some(Sale {
+              date: SaleDate::MonthAndDay(10, 1),
+                sale_price: SalePrice::PercentageOff(0.25)
+            }),
           },
           Item {
             id: 3,
@@ -350,22 +2463,1407 @@ mod tests {
                 amount: 6,
                 total_price: 6.0,
             }),
+            age_restricted: None,
+            allergens: vec![],
+            nutrition: None,
+            available_from: None,
+            cost: None,
+            floor_price: None,
             sale: Some(Sale {
               date: SaleDate::DayOfWeek(chrono::Weekday::Fri),
               sale_price: SalePrice::QuantityForFixedPrice(8, 6.0)
               },),
           },
-      ];
+      ]);
 
         let mut cart = ShoppingCart::new();
-        cart.add("Cookie", 8);
-        cart.add("Key Lime Cheesecake", 4);
+        cart.add("Cookie", 8).unwrap();
+        cart.add("Key Lime Cheesecake", 4).unwrap();
         assert_eq!(
             cart.total(
-                &data,
+                &catalog,
                 &chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap()
             ),
             30.0
         );
     }
+
+    #[test]
+    fn test_pickup_date_drives_sales() {
+        let catalog = Catalog::new(vec![ItemBuilder::cookie()
+            .with_sale(
+                SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                SalePrice::QuantityForFixedPrice(8, 6.0),
+            )
+            .build()]);
+        let thursday = chrono::NaiveDate::from_ymd_opt(2021, 9, 30).unwrap();
+        let friday = chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 8).unwrap();
+        assert_eq!(cart.total(&catalog, &thursday), 10.0);
+
+        cart.set_pickup(friday.and_hms_opt(9, 0, 0).unwrap());
+        assert_eq!(cart.total(&catalog, &thursday), 6.0);
+    }
+
+    #[test]
+    fn test_checkout_lines() {
+        let catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Brownie", 4).unwrap();
+        cart.add("Cookie", 7).unwrap();
+        assert_eq!(
+            cart.checkout_lines(&["Brownie", "Cupcake"], &catalog, dummy_date),
+            Err(CartError::NotInCart {
+                product: "Cupcake".to_string(),
+            })
+        );
+        assert_eq!(cart.total(&catalog, dummy_date), 14.25);
+        assert_eq!(
+            cart.checkout_lines(&["Brownie"], &catalog, dummy_date),
+            Ok(7.0)
+        );
+        assert_eq!(cart.total(&catalog, dummy_date), 7.25);
+        assert_eq!(
+            cart.checkout_lines(&["Cookie"], &catalog, dummy_date),
+            Ok(7.25)
+        );
+        assert_eq!(cart.total(&catalog, dummy_date), 0.0);
+    }
+
+    #[test]
+    fn test_checkout_lines_applies_promotions() {
+        let mut catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        catalog
+            .add_promotion(
+                access::Role::Admin,
+                Promotion::CheapestFree {
+                    qualifying: vec![1, 3],
+                    buy: 3,
+                },
+            )
+            .unwrap();
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 6).unwrap();
+        cart.add("Brownie", 1).unwrap();
+        let mut cookies = cart.select_lines(&["Cookie"]).unwrap();
+        assert_eq!(cookies.price(&catalog, dummy_date), 5.0);
+        assert_eq!(
+            cart.checkout_lines(&["Cookie"], &catalog, dummy_date),
+            Ok(5.0)
+        );
+        assert_eq!(
+            cart.checkout_cash(&["Brownie"], &catalog, dummy_date, 2.0),
+            Ok(cash::CashPayment {
+                total: 2.0,
+                due: 2.0,
+                tendered: 2.0,
+                change: 0.0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_preorders() {
+        let halloween = chrono::NaiveDate::from_ymd_opt(2021, 10, 31).unwrap();
+        let catalog = Catalog::new(
+            CatalogBuilder::bakery_demo()
+                .with_item(ItemBuilder::new(5, "Pumpkin Pie", 15.0).with_available_from(halloween))
+                .build(),
+        );
+        let today = chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Pumpkin Pie", 1).unwrap();
+        cart.add("Cookie", 2).unwrap();
+        assert_eq!(
+            cart.preorders(&catalog.items, &today),
+            vec![("Pumpkin Pie", halloween)]
+        );
+        assert_eq!(
+            cart.checkout_lines(&["Pumpkin Pie", "Cookie"], &catalog, &today),
+            Err(CartError::NotYetAvailable {
+                product: "Pumpkin Pie".to_string(),
+                available_from: halloween,
+            })
+        );
+
+        cart.set_pickup(halloween.and_hms_opt(10, 0, 0).unwrap());
+        assert_eq!(
+            cart.checkout_lines(&["Pumpkin Pie", "Cookie"], &catalog, &today),
+            Ok(17.5)
+        );
+    }
+
+    #[test]
+    fn test_age_restricted_items() {
+        let catalog = Catalog::new(vec![ItemBuilder::new(5, "Rum Cake", 12.0)
+            .with_age_restriction(21)
+            .build()]);
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+        let refused = CartError::AgeVerificationRequired {
+            product: "Rum Cake".to_string(),
+            minimum_age: 21,
+        };
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Rum Cake", 1).unwrap();
+        assert_eq!(cart.check_age(&catalog.items), Err(refused.clone()));
+        assert_eq!(
+            cart.checkout_lines(&["Rum Cake"], &catalog, dummy_date),
+            Err(refused.clone())
+        );
+
+        cart.set_verified_age(19);
+        assert_eq!(cart.check_age(&catalog.items), Err(refused));
+
+        cart.set_verified_age(21);
+        assert_eq!(cart.check_age(&catalog.items), Ok(()));
+        assert_eq!(
+            cart.checkout_lines(&["Rum Cake"], &catalog, dummy_date),
+            Ok(12.0)
+        );
+    }
+
+    #[test]
+    fn test_cart_limits() {
+        let catalog = Catalog::new(vec![ItemBuilder::mini_gingerbread_donut().build()]);
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        assert_eq!(
+            cart.add("Mini Gingerbread Donut", 10_000),
+            Err(CartError::TooManyUnits {
+                product: "Mini Gingerbread Donut".to_string(),
+                max: 1_000,
+            })
+        );
+
+        cart.set_limits(CartLimits {
+            max_lines: 1,
+            max_units_per_line: 1_000,
+            max_order_value: 100.0,
+        });
+        cart.add("Mini Gingerbread Donut", 1_000).unwrap();
+        cart.add("Mini Gingerbread Donut", 500).unwrap();
+        assert_eq!(
+            cart.add("Cookie", 1),
+            Err(CartError::TooManyLines { max: 1 })
+        );
+        assert_eq!(
+            cart.checkout_lines(&["Mini Gingerbread Donut"], &catalog, dummy_date),
+            Err(CartError::OrderValueExceeded {
+                total: 250.0,
+                max: 100.0,
+            })
+        );
+        assert_eq!(cart.total(&catalog, dummy_date), 250.0);
+    }
+
+    #[test]
+    fn test_unit_price_display() {
+        let cookie = ItemBuilder::cookie().with_bulk(6, 6.0).build();
+        assert_eq!(
+            cookie.unit_price_display(),
+            "$1.25/cookie, $1.00/cookie with bulk deal"
+        );
+
+        let cheesecake = ItemBuilder::key_lime_cheesecake().build();
+        assert_eq!(cheesecake.unit_price_display(), "$8.00/key lime cheesecake");
+    }
+
+    #[test]
+    fn test_allergen_summary() {
+        let mut cookie = ItemBuilder::cookie()
+            .with_allergens(&["eggs", "milk", "wheat"])
+            .build();
+        cookie.nutrition = Some(serde_json::json!({ "calories": 210 }));
+        let data = vec![
+            ItemBuilder::brownie()
+                .with_allergens(&["eggs", "tree nuts", "wheat"])
+                .build(),
+            cookie,
+        ];
+
+        let mut cart = ShoppingCart::new();
+        assert!(cart.allergen_summary(&data).is_empty());
+
+        cart.add("Cookie", 2).unwrap();
+        cart.add("Brownie", 1).unwrap();
+        assert_eq!(
+            cart.allergen_summary(&data).into_iter().collect::<Vec<_>>(),
+            vec!["eggs", "milk", "tree nuts", "wheat"]
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let missing_price = r#"
+        {
+            "treats": [
+              { "id": 1, "name": "Brownie", "imageURL": "https://example.com/brownie.jpg", "price": 2.0 },
+              { "id": 2, "name": "Key Lime Cheesecake", "imageURL": "https://example.com/cheesecake.jpg" }
+            ]
+        }
+        "#;
+        assert!(parse(missing_price)
+            .unwrap_err()
+            .to_string()
+            .starts_with("treats[1]: missing field `price`"));
+
+        let bad_image = r#"{ "treats": [{ "id": 1, "name": "Brownie", "imageURL": "brownie.jpg", "price": 2.0 }] }"#;
+        assert!(parse(bad_image)
+            .unwrap_err()
+            .to_string()
+            .starts_with("treats[0]: image URL `brownie.jpg` is not a valid URL"));
+
+        let april_31 = r#"{ "treats": [{ "id": 1, "name": "Brownie", "imageURL": "https://example.com/brownie.jpg", "price": 2.0, "sale": { "date": { "MonthAndDay": [4, 31] }, "sale_price": "TwoForOne" } }] }"#;
+        assert!(parse(april_31)
+            .unwrap_err()
+            .to_string()
+            .starts_with("treats[0]: the sale is on a date that never occurs"));
+
+        let too_generous = r#"{ "treats": [{ "id": 1, "name": "Brownie", "imageURL": "https://example.com/brownie.jpg", "price": 2.0, "sale": { "date": "LastDayOfMonth", "sale_price": { "PercentageOff": 25 } } }] }"#;
+        assert!(parse(too_generous)
+            .unwrap_err()
+            .to_string()
+            .starts_with("treats[0]: the sale is 2500% off, which isn't between 0% and 100%"));
+        let twelve_and_a_half = too_generous.replace("25 }", "0.125 }");
+        assert!(parse(&twelve_and_a_half).is_ok());
+
+        let bulk_of_none = r#"{ "treats": [{ "id": 1, "name": "Brownie", "imageURL": "https://example.com/brownie.jpg", "price": 2.0, "bulkPricing": { "amount": 0, "totalPrice": 7.0 } }] }"#;
+        assert!(parse(bulk_of_none)
+            .unwrap_err()
+            .to_string()
+            .starts_with("treats[0]: bulk pricing needs groups of at least 1 item"));
+        let none_for_five = r#"{ "treats": [{ "id": 1, "name": "Brownie", "imageURL": "https://example.com/brownie.jpg", "price": 2.0, "sale": { "date": "LastDayOfMonth", "sale_price": { "QuantityForFixedPrice": [0, 5.0] } } }] }"#;
+        assert!(parse(none_for_five)
+            .unwrap_err()
+            .to_string()
+            .starts_with("treats[0]: the sale is on groups of 0 items"));
+
+        assert!(parse(r#"{ "products": [] }"#).is_err());
+        assert!(parse(r#"{ "treats": {} }"#).is_err());
+    }
+
+    #[test]
+    fn test_parse_unknown_fields() {
+        let json_data = r#"{ "treats": [{ "id": 1, "name": "Brownie", "imageURL": "https://example.com/brownie.jpg", "price": 2.0, "colour": "brown" }] }"#;
+        assert_eq!(parse(json_data).is_err(), cfg!(feature = "strict-catalog"));
+    }
+
+    #[test]
+    fn test_catalog_editing() {
+        let mut catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Key Lime Cheesecake", 1).unwrap();
+        assert!(cart.is_stale(&catalog));
+        assert_eq!(cart.price(&catalog, dummy_date), 8.0);
+        assert!(!cart.is_stale(&catalog));
+
+        assert_eq!(
+            catalog
+                .set_price(access::Role::Manager, 2, 9.0)
+                .unwrap_err()
+                .to_string(),
+            "permission denied: Manager can't perform EditCatalog"
+        );
+        assert!(!cart.is_stale(&catalog));
+        catalog.set_price(access::Role::Admin, 2, 9.0).unwrap();
+        assert!(cart.is_stale(&catalog));
+        assert_eq!(cart.price(&catalog, dummy_date), 9.0);
+
+        catalog
+            .add_sale(
+                access::Role::Admin,
+                2,
+                Sale {
+                    date: SaleDate::MonthAndDay(1, 1),
+                    sale_price: SalePrice::PercentageOff(0.5),
+                },
+            )
+            .unwrap();
+        assert_eq!(cart.price(&catalog, dummy_date), 4.5);
+
+        catalog
+            .upsert_item(
+                access::Role::Admin,
+                ItemBuilder::key_lime_cheesecake().build(),
+            )
+            .unwrap();
+        catalog
+            .upsert_item(
+                access::Role::Admin,
+                ItemBuilder::new(5, "Croissant", 3.0).build(),
+            )
+            .unwrap();
+        assert_eq!(
+            catalog.upsert_item(
+                access::Role::Admin,
+                ItemBuilder::new(6, "Croissant", 3.5).build()
+            ),
+            Err(CatalogError::DuplicateName("Croissant".to_string()))
+        );
+        assert_eq!(catalog.items.len(), 5);
+        assert_eq!(cart.price(&catalog, dummy_date), 8.0);
+
+        assert_eq!(
+            catalog.remove_item(access::Role::Admin, 5).unwrap().name,
+            "Croissant"
+        );
+        assert_eq!(
+            catalog.remove_item(access::Role::Admin, 5).unwrap_err(),
+            CatalogError::UnknownItem(5)
+        );
+        assert_eq!(
+            catalog.set_price(access::Role::Admin, 5, 1.0),
+            Err(CatalogError::UnknownItem(5))
+        );
+        assert_eq!(catalog.version, 6);
+
+        let path = std::env::temp_dir().join("shopping-cart-test-catalog.json");
+        catalog.save(&path).unwrap();
+        let saved = parse(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved.len(), 4);
+        assert_eq!(saved[1].price, 8.0);
+    }
+
+    #[test]
+    fn test_duplicate_and_template_items() {
+        let mut catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+
+        assert_eq!(
+            catalog.duplicate_item(access::Role::Admin, 3, "Snickerdoodle"),
+            Ok(5)
+        );
+        assert_eq!(
+            catalog.duplicate_item(access::Role::Admin, 3, "Snickerdoodle"),
+            Err(CatalogError::DuplicateName("Snickerdoodle".to_string()))
+        );
+        assert_eq!(
+            catalog.duplicate_item(access::Role::Admin, 9, "Macaron"),
+            Err(CatalogError::UnknownItem(9))
+        );
+
+        let cookie = ItemTemplate::from(&catalog.items[2]);
+        catalog
+            .add_template(access::Role::Admin, "cookie", cookie)
+            .unwrap();
+        assert_eq!(
+            catalog.add_from_template(
+                access::Role::Admin,
+                "cookie",
+                "Oatmeal Raisin",
+                "https://example.com/oatmeal-raisin.jpg"
+            ),
+            Ok(6)
+        );
+        assert_eq!(
+            catalog.add_from_template(
+                access::Role::Admin,
+                "cake",
+                "Pound Cake",
+                "https://example.com/pound-cake.jpg"
+            ),
+            Err(CatalogError::UnknownTemplate("cake".to_string()))
+        );
+
+        let oatmeal_raisin = &catalog.items[5];
+        assert_eq!(oatmeal_raisin.price, 1.25);
+        assert_eq!(oatmeal_raisin.bulk_pricing.as_ref().unwrap().amount, 6);
+        assert_eq!(catalog.items[4].image_url, catalog.items[2].image_url);
+    }
+
+    #[test]
+    fn test_adjust_prices() {
+        let mut catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        catalog.set_price(access::Role::Admin, 4, 0.5).unwrap();
+        let everything = |_: &Item| true;
+
+        let preview =
+            catalog.preview_price_adjustment(everything, PriceAdjustment::Percentage(0.03), 0.05);
+        assert_eq!(
+            preview,
+            vec![
+                PriceChange {
+                    item_id: 1,
+                    was: 2.0,
+                    now: 2.05,
+                },
+                PriceChange {
+                    item_id: 2,
+                    was: 8.0,
+                    now: 8.25,
+                },
+                PriceChange {
+                    item_id: 3,
+                    was: 1.25,
+                    now: 1.3,
+                },
+            ]
+        );
+        assert_eq!(catalog.items[0].price, 2.0);
+        assert_eq!(catalog.version, 2);
+
+        assert_eq!(
+            catalog.adjust_prices(
+                access::Role::Admin,
+                everything,
+                PriceAdjustment::Percentage(0.03),
+                0.05
+            ),
+            Ok(preview)
+        );
+        assert_eq!(catalog.items[1].price, 8.25);
+        assert_eq!(catalog.version, 3);
+
+        let cheap = |item: &Item| item.price < 2.0;
+        assert_eq!(
+            catalog.adjust_prices(
+                access::Role::Admin,
+                cheap,
+                PriceAdjustment::Fixed(-2.0),
+                0.0
+            ),
+            Ok(vec![
+                PriceChange {
+                    item_id: 3,
+                    was: 1.3,
+                    now: 0.0,
+                },
+                PriceChange {
+                    item_id: 4,
+                    was: 0.5,
+                    now: 0.0,
+                },
+            ])
+        );
+        assert_eq!(catalog.price_log.len(), 6);
+        assert_eq!(
+            catalog.adjust_prices(
+                access::Role::Manager,
+                cheap,
+                PriceAdjustment::Fixed(1.0),
+                0.0
+            ),
+            Err(CatalogError::PermissionDenied(access::PermissionDenied {
+                role: access::Role::Manager,
+                permission: access::Permission::EditCatalog,
+            }))
+        );
+        assert_eq!(catalog.items[2].price, 0.0);
+    }
+
+    #[test]
+    fn test_scheduled_prices() {
+        let mut catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let september = chrono::NaiveDate::from_ymd_opt(2021, 9, 30).unwrap();
+        let october = chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let november = chrono::NaiveDate::from_ymd_opt(2021, 11, 1).unwrap();
+
+        catalog
+            .schedule_price(access::Role::Admin, 3, 1.5, october)
+            .unwrap();
+        catalog
+            .schedule_price(access::Role::Admin, 3, 1.75, november)
+            .unwrap();
+        catalog
+            .schedule_price(access::Role::Admin, 1, 2.5, november)
+            .unwrap();
+        assert_eq!(
+            catalog.schedule_price(access::Role::Admin, 9, 1.0, october),
+            Err(CatalogError::UnknownItem(9))
+        );
+
+        assert!(catalog.prices_on(&september).is_empty());
+        assert_eq!(
+            catalog.prices_on(&november),
+            std::collections::HashMap::from([(3, 1.75), (1, 2.5)])
+        );
+        assert_eq!(catalog.rescheduled_items(&october).len(), 1);
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 2).unwrap();
+        assert_eq!(cart.price(&catalog, &september), 2.5);
+        assert_eq!(cart.price(&catalog, &october), 3.0);
+        assert_eq!(cart.price(&catalog, &november), 3.5);
+        assert_eq!(cart.total(&catalog, &october), 3.0);
+        assert_eq!(cart.breakdown(&catalog, &october)[0].total, 3.0);
+        assert_eq!(
+            pricing::simulate(&cart, &catalog, &october, &pricing::Scenario::default()),
+            3.0
+        );
+        let mut pickup_in_november = cart.select_lines(&["Cookie"]).unwrap();
+        pickup_in_november.set_pickup(november.and_hms_opt(9, 0, 0).unwrap());
+        let carts = [cart.select_lines(&["Cookie"]).unwrap(), pickup_in_november];
+        assert_eq!(
+            pricing::batch_totals(&carts, &catalog, &october),
+            vec![3.0, 3.5]
+        );
+
+        assert_eq!(
+            catalog
+                .cancel_scheduled_price(access::Role::Admin, 3, november)
+                .unwrap()
+                .price,
+            1.75
+        );
+        assert_eq!(
+            catalog.cancel_scheduled_price(access::Role::Admin, 3, november),
+            Err(CatalogError::NoScheduledPrice {
+                item_id: 3,
+                effective: november,
+            })
+        );
+        assert_eq!(cart.price(&catalog, &november), 3.0);
+
+        catalog
+            .schedule_price(access::Role::Admin, 3, 2.0, november)
+            .unwrap();
+        assert!(cart.is_stale(&catalog));
+        assert_eq!(
+            cart.reprice(&catalog, &november, false),
+            [RepriceAdjustment::PriceChanged {
+                product: "Cookie".to_string(),
+                was: 1.5,
+                now: 2.0,
+            }]
+        );
+        assert!(cart.reprice(&catalog, &november, false).is_empty());
+        catalog
+            .cancel_scheduled_price(access::Role::Admin, 3, november)
+            .unwrap();
+        assert!(cart.is_stale(&catalog));
+        cart.price(&catalog, &october);
+        assert!(cart.reprice(&catalog, &october, false).is_empty());
+
+        catalog.apply_scheduled_prices(&october);
+        assert_eq!(catalog.items[2].price, 1.5);
+        assert_eq!(catalog.items[0].price, 2.0);
+        assert_eq!(catalog.scheduled_prices.len(), 1);
+        assert_eq!(
+            catalog.audit_log.about("item 3").last(),
+            Some(&audit::AuditEntry {
+                actor: audit::Actor::System,
+                action: "set_price",
+                subject: "item 3".to_string(),
+                before: Some(serde_json::json!(1.25)),
+                after: Some(serde_json::json!(1.5)),
+            })
+        );
+        assert_eq!(
+            catalog
+                .audit_log
+                .entries()
+                .iter()
+                .filter(|entry| entry.actor == audit::Actor::Staff(access::Role::Admin))
+                .count(),
+            6
+        );
+        assert_eq!(
+            cart.checkout_lines(&["Cookie"], &catalog, &october),
+            Ok(3.0)
+        );
+    }
+
+    #[test]
+    fn test_soft_delete_and_restore() {
+        let mut catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+        let deleted_at = chrono::NaiveDate::from_ymd_opt(2021, 10, 1)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Brownie", 1).unwrap();
+        cart.add("Cookie", 2).unwrap();
+        assert_eq!(cart.price(&catalog, dummy_date), 4.5);
+
+        catalog
+            .soft_delete_item(access::Role::Admin, 3, deleted_at)
+            .unwrap();
+        assert_eq!(
+            cart.reprice(&catalog, dummy_date, true),
+            [RepriceAdjustment::TemporarilyUnavailable {
+                product: "Cookie".to_string(),
+            }]
+        );
+        assert_eq!(cart.price(&catalog, dummy_date), 2.0);
+        assert_eq!(
+            cart.checkout_lines(&["Cookie"], &catalog, dummy_date),
+            Err(CartError::TemporarilyUnavailable {
+                product: "Cookie".to_string(),
+            })
+        );
+
+        assert_eq!(
+            catalog.add_item(access::Role::Admin, ItemBuilder::cookie().build()),
+            Err(CatalogError::DuplicateName("Cookie".to_string()))
+        );
+        assert_eq!(
+            catalog.upsert_item(
+                access::Role::Admin,
+                ItemBuilder::new(9, "Cookie", 1.0).build()
+            ),
+            Err(CatalogError::DuplicateName("Cookie".to_string()))
+        );
+        catalog
+            .upsert_item(
+                access::Role::Admin,
+                ItemBuilder::new(3, "Macaron", 2.5).build(),
+            )
+            .unwrap();
+        assert_eq!(
+            catalog.restore_item(access::Role::Admin, 3),
+            Err(CatalogError::DuplicateItem(3))
+        );
+        catalog.remove_item(access::Role::Admin, 3).unwrap();
+        catalog
+            .items
+            .push(ItemBuilder::new(7, "Cookie", 1.0).build());
+        assert_eq!(
+            catalog.restore_item(access::Role::Admin, 3),
+            Err(CatalogError::DuplicateName("Cookie".to_string()))
+        );
+        catalog.remove_item(access::Role::Admin, 7).unwrap();
+        assert_eq!(catalog.restore_item(access::Role::Admin, 3), Ok(()));
+        assert_eq!(
+            catalog.restore_item(access::Role::Admin, 3),
+            Err(CatalogError::UnknownItem(3))
+        );
+        assert_eq!(cart.price(&catalog, dummy_date), 4.5);
+
+        catalog
+            .soft_delete_item(access::Role::Admin, 3, deleted_at)
+            .unwrap();
+        assert!(catalog
+            .purge_trash(deleted_at + chrono::Days::new(29), 30)
+            .is_empty());
+        assert_eq!(
+            catalog.purge_trash(deleted_at + chrono::Days::new(30), 30)[0].name,
+            "Cookie"
+        );
+        assert_eq!(
+            cart.reprice(&catalog, dummy_date, true),
+            [RepriceAdjustment::Removed {
+                product: "Cookie".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_reprice_after_catalog_change() {
+        let mut catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Brownie", 1).unwrap();
+        cart.add("Cookie", 2).unwrap();
+        cart.add("Mini Gingerbread Donut", 2).unwrap();
+        assert_eq!(cart.price(&catalog, dummy_date), 5.5);
+
+        catalog.set_price(access::Role::Admin, 3, 1.5).unwrap();
+        catalog.remove_item(access::Role::Admin, 4).unwrap();
+        assert_eq!(
+            cart.reprice(&catalog, dummy_date, false),
+            [
+                RepriceAdjustment::PriceChanged {
+                    product: "Cookie".to_string(),
+                    was: 1.25,
+                    now: 1.5,
+                },
+                RepriceAdjustment::Unavailable {
+                    product: "Mini Gingerbread Donut".to_string(),
+                },
+            ]
+        );
+        assert!(!cart.is_stale(&catalog));
+        assert_eq!(cart.price(&catalog, dummy_date), 5.0);
+
+        assert_eq!(
+            cart.reprice(&catalog, dummy_date, true),
+            [RepriceAdjustment::Removed {
+                product: "Mini Gingerbread Donut".to_string(),
+            }]
+        );
+        assert_eq!(cart.products.len(), 2);
+        assert!(cart.reprice(&catalog, dummy_date, true).is_empty());
+    }
+
+    #[test]
+    fn test_lines_for_items_gone_from_the_catalog() {
+        let mut catalog = Catalog::new(vec![
+            ItemBuilder::brownie().with_allergens(&["eggs"]).build(),
+            ItemBuilder::new(5, "Rum Cake", 12.0)
+                .with_age_restriction(21)
+                .with_allergens(&["milk"])
+                .build(),
+        ]);
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Brownie", 1).unwrap();
+        cart.add("Rum Cake", 1).unwrap();
+        catalog.remove_item(access::Role::Admin, 5).unwrap();
+        cart.reprice(&catalog, dummy_date, false);
+        assert_eq!(
+            cart.allergen_summary(&catalog.items)
+                .into_iter()
+                .collect::<Vec<_>>(),
+            vec!["eggs"]
+        );
+        assert_eq!(cart.check_age(&catalog.items), Ok(()));
+        assert_eq!(
+            cart.checkout_lines(&["Brownie", "Rum Cake"], &catalog, dummy_date),
+            Err(CartError::Unavailable {
+                product: "Rum Cake".to_string(),
+            })
+        );
+        assert_eq!(cart.products.len(), 2);
+        assert_eq!(
+            cart.checkout_lines(&["Brownie"], &catalog, dummy_date),
+            Ok(2.0)
+        );
+    }
+
+    #[test]
+    fn test_lint_sales() {
+        let catalog = Catalog::new(vec![
+            ItemBuilder::brownie()
+                .with_sale(
+                    SaleDate::MonthAndDay(2, 30),
+                    SalePrice::QuantityForFixedPrice(4, 10.0),
+                )
+                .build(),
+            ItemBuilder::key_lime_cheesecake()
+                .with_sale(SaleDate::MonthAndDay(2, 29), SalePrice::PercentageOff(1.0))
+                .build(),
+            ItemBuilder::cookie()
+                .with_bulk(6, 6.0)
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                    SalePrice::QuantityForFixedPrice(8, 6.0),
+                )
+                .build(),
+            ItemBuilder::mini_gingerbread_donut()
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Tue),
+                    SalePrice::TwoForOne,
+                )
+                .with_cost(0.3)
+                .build(),
+        ]);
+
+        assert_eq!(
+            catalog.lint_sales(),
+            vec![
+                SaleWarning::NeverFires { item_id: 1 },
+                SaleWarning::NoDiscount { item_id: 1 },
+                SaleWarning::GivesItemAway { item_id: 2 },
+                SaleWarning::OverlapsBulkPricing { item_id: 3 },
+                SaleWarning::BelowCost { item_id: 4 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_buy_one_get_one_percent_off() {
+        let catalog = Catalog::new(vec![ItemBuilder::brownie()
+            .with_sale(
+                SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                SalePrice::BuyOneGetOnePercentOff(0.5),
+            )
+            .build()]);
+        let friday = &chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let thursday = &chrono::NaiveDate::from_ymd_opt(2021, 9, 30).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Brownie", 1).unwrap();
+        assert_eq!(cart.total(&catalog, friday), 2.0);
+        cart.add("Brownie", 2).unwrap();
+        assert_eq!(cart.total(&catalog, friday), 3.0);
+        cart.add("Brownie", 5).unwrap();
+        assert_eq!(cart.total(&catalog, friday), 8.0);
+        assert_eq!(cart.total(&catalog, thursday), 10.0);
+    }
+
+    #[test]
+    fn test_fixed_unit_price_sale() {
+        let catalog = Catalog::new(vec![ItemBuilder::mini_gingerbread_donut()
+            .with_sale(
+                SaleDate::DayOfWeek(chrono::Weekday::Tue),
+                SalePrice::FixedUnitPrice(0.25),
+            )
+            .build()]);
+        let tuesday = &chrono::NaiveDate::from_ymd_opt(2021, 9, 28).unwrap();
+        let wednesday = &chrono::NaiveDate::from_ymd_opt(2021, 9, 29).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Mini Gingerbread Donut", 1).unwrap();
+        assert_eq!(cart.total(&catalog, tuesday), 0.25);
+        cart.add("Mini Gingerbread Donut", 7).unwrap();
+        assert_eq!(cart.total(&catalog, tuesday), 1.75);
+        assert_eq!(cart.total(&catalog, wednesday), 3.5);
+
+        let negative = r#"{ "treats": [{ "id": 1, "name": "Brownie", "imageURL": "https://example.com/brownie.jpg", "price": 2.0, "sale": { "date": { "DayOfWeek": "Tue" }, "sale_price": { "FixedUnitPrice": -1.0 } } }] }"#;
+        assert!(parse(negative)
+            .unwrap_err()
+            .to_string()
+            .starts_with("treats[0]: the sale price of -1 is negative"));
+    }
+
+    #[test]
+    fn test_spend_and_get_promotion() {
+        let mut catalog = Catalog::new(
+            CatalogBuilder::bakery_demo()
+                .with_item(ItemBuilder::new(5, "Coffee", 3.0))
+                .build(),
+        );
+        catalog
+            .add_promotion(
+                access::Role::Admin,
+                Promotion::SpendAndGet {
+                    qualifying: vec![1, 2],
+                    threshold: 25.0,
+                    reward: 5,
+                    reward_price: 0.0,
+                },
+            )
+            .unwrap();
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Key Lime Cheesecake", 2).unwrap();
+        cart.add("Cookie", 8).unwrap();
+        assert!(cart.promotional_lines(&catalog, dummy_date).is_empty());
+
+        cart.add("Key Lime Cheesecake", 4).unwrap();
+        assert_eq!(
+            cart.promotional_lines(&catalog, dummy_date),
+            vec![PromotionalLine {
+                item_id: 5,
+                name: "Coffee".to_string(),
+                price: 0.0,
+                gift: false,
+            }]
+        );
+        assert_eq!(cart.price(&catalog, dummy_date), 40.5);
+
+        cart.set_line_price_override(access::Role::Manager, "Key Lime Cheesecake", 4.0, "seconds")
+            .unwrap();
+        assert!(cart.promotional_lines(&catalog, dummy_date).is_empty());
+    }
+
+    #[test]
+    fn test_gift_with_purchase() {
+        let mut catalog = Catalog::new(
+            CatalogBuilder::bakery_demo()
+                .with_item(ItemBuilder::new(5, "Tote Bag", 10.0))
+                .build(),
+        );
+        catalog
+            .add_promotion(
+                access::Role::Admin,
+                Promotion::GiftWithPurchase {
+                    qualifying: vec![1, 3],
+                    min_units: 10,
+                    gift: 5,
+                },
+            )
+            .unwrap();
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+        let tote_bag = PromotionalLine {
+            item_id: 5,
+            name: "Tote Bag".to_string(),
+            price: 0.0,
+            gift: true,
+        };
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Brownie", 4).unwrap();
+        cart.add("Cookie", 6).unwrap();
+        assert_eq!(cart.promotional_lines(&catalog, dummy_date), vec![tote_bag]);
+        assert_eq!(cart.price(&catalog, dummy_date), 13.0);
+
+        cart.add("Cookie", 5).unwrap();
+        assert!(cart.promotional_lines(&catalog, dummy_date).is_empty());
+        assert_eq!(cart.price(&catalog, dummy_date), 13.25);
+    }
+
+    #[test]
+    fn test_line_breakdown() {
+        let catalog = Catalog::new(
+            CatalogBuilder::bakery_demo()
+                .with_item(
+                    ItemBuilder::new(5, "Eclair", 3.0)
+                        .with_sale(
+                            SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                            SalePrice::PercentageOff(0.5),
+                        )
+                        .with_floor_price(2.0),
+                )
+                .with_item(ItemBuilder::new(6, "Scone", 1.25).with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                    SalePrice::PercentageOff(0.29),
+                ))
+                .build(),
+        );
+        let friday = &chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 8).unwrap();
+        cart.add("Mini Gingerbread Donut", 3).unwrap();
+        cart.add("Eclair", 2).unwrap();
+        cart.add("Brownie", 1).unwrap();
+        cart.add("Scone", 2).unwrap();
+        cart.set_line_price_override(access::Role::Manager, "Brownie", 1.0, "day old")
+            .unwrap();
+
+        let breakdown = cart.breakdown(&catalog, friday);
+        let explanations: Vec<&str> = breakdown
+            .iter()
+            .map(|line| line.explanation.as_str())
+            .collect();
+        assert_eq!(
+            explanations,
+            vec![
+                "6 @ bulk 6-for-$6.00, 2 @ $1.25",
+                "3 @ $0.50",
+                "2 @ $1.50 (50% off), raised to the $2.00 floor price",
+                "1 @ $1.00 (price override: day old)",
+                "2 @ $0.89 (29% off)",
+            ]
+        );
+        assert_eq!(
+            breakdown.iter().map(|line| line.total).sum::<f64>(),
+            cart.total(&catalog, friday)
+        );
+        assert_eq!(
+            serde_json::to_value(&breakdown[0]).unwrap(),
+            serde_json::json!({
+                "product": "Cookie",
+                "quantity": 8,
+                "total": 8.5,
+                "explanation": "6 @ bulk 6-for-$6.00, 2 @ $1.25",
+            })
+        );
+    }
+
+    #[test]
+    fn test_weekday_set_sales() {
+        let json_data = r#"{ "treats": [{ "id": 1, "name": "Brownie", "imageURL": "https://example.com/brownie.jpg", "price": 2.0, "bulkPricing": null, "sale": { "date": { "Weekdays": ["Sat", "Sun"] }, "sale_price": "TwoForOne" } }] }"#;
+        let catalog = Catalog::new(parse(json_data).unwrap());
+        let friday = &chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let saturday = &chrono::NaiveDate::from_ymd_opt(2021, 10, 2).unwrap();
+        let sunday = &chrono::NaiveDate::from_ymd_opt(2021, 10, 3).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Brownie", 2).unwrap();
+        assert_eq!(cart.total(&catalog, friday), 4.0);
+        assert_eq!(cart.total(&catalog, saturday), 2.0);
+        assert_eq!(cart.total(&catalog, sunday), 2.0);
+
+        let sale = catalog.items[0].sale.as_ref().unwrap();
+        assert_eq!(
+            sale.time_remaining(saturday.and_hms_opt(12, 0, 0).unwrap()),
+            Some(chrono::Duration::hours(36))
+        );
+    }
+
+    #[test]
+    fn test_leap_day_and_end_of_month_sales() {
+        let leap_day = SaleDate::MonthAndDay(2, 29);
+        assert!(leap_day.matches(&chrono::NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()));
+        assert!(!leap_day.matches(&chrono::NaiveDate::from_ymd_opt(2024, 2, 28).unwrap()));
+        assert!(leap_day.matches(&chrono::NaiveDate::from_ymd_opt(2021, 2, 28).unwrap()));
+        assert!(!leap_day.matches(&chrono::NaiveDate::from_ymd_opt(2021, 3, 1).unwrap()));
+        assert!(!leap_day.never_fires());
+
+        let last_day = SaleDate::LastDayOfMonth;
+        for (year, month, day) in [(2021, 2, 28), (2024, 2, 29), (2021, 4, 30), (2021, 12, 31)] {
+            let date = chrono::NaiveDate::from_ymd_opt(year, month, day).unwrap();
+            assert!(last_day.matches(&date));
+            assert!(!last_day.matches(&date.pred_opt().unwrap()));
+        }
+    }
+
+    #[test]
+    fn test_composite_sale_dates() {
+        let fridays_in_october = SaleDate::All(vec![
+            SaleDate::DayOfWeek(chrono::Weekday::Fri),
+            SaleDate::Month(10),
+        ]);
+        let october_1 = &chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let october_2 = &chrono::NaiveDate::from_ymd_opt(2021, 10, 2).unwrap();
+        let october_3 = &chrono::NaiveDate::from_ymd_opt(2021, 10, 3).unwrap();
+        let september_24 = &chrono::NaiveDate::from_ymd_opt(2021, 9, 24).unwrap();
+        assert!(fridays_in_october.matches(october_1));
+        assert!(!fridays_in_october.matches(october_2));
+        assert!(!fridays_in_october.matches(september_24));
+
+        let fridays_in_october_or_sundays = SaleDate::Any(vec![
+            fridays_in_october,
+            SaleDate::DayOfWeek(chrono::Weekday::Sun),
+        ]);
+        assert!(fridays_in_october_or_sundays.matches(october_1));
+        assert!(!fridays_in_october_or_sundays.matches(october_2));
+        assert!(fridays_in_october_or_sundays.matches(october_3));
+        assert!(!fridays_in_october_or_sundays.never_fires());
+
+        assert!(SaleDate::Any(vec![]).never_fires());
+        assert!(SaleDate::All(vec![SaleDate::Month(13), SaleDate::Month(10)]).never_fires());
+        assert!(!SaleDate::All(vec![]).never_fires());
+    }
+
+    #[test]
+    fn test_active_sales() {
+        let catalog = Catalog::new(vec![
+            ItemBuilder::key_lime_cheesecake()
+                .with_sale(SaleDate::MonthAndDay(10, 1), SalePrice::PercentageOff(0.25))
+                .build(),
+            ItemBuilder::cookie()
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Sat),
+                    SalePrice::QuantityForFixedPrice(8, 6.0),
+                )
+                .build(),
+        ]);
+        let october_1 = chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let now = october_1.and_hms_opt(21, 30, 0).unwrap();
+
+        assert_eq!(
+            catalog.active_sales(now),
+            vec![ActiveSale {
+                item_id: 2,
+                starts: october_1.and_hms_opt(0, 0, 0).unwrap(),
+                ends: october_1.succ_opt().unwrap().and_hms_opt(0, 0, 0).unwrap(),
+            }]
+        );
+        let sale = catalog.items[0].sale.as_ref().unwrap();
+        assert_eq!(
+            sale.time_remaining(now),
+            Some(chrono::Duration::minutes(150))
+        );
+        assert_eq!(
+            sale.time_remaining(now + chrono::Duration::minutes(150)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_floor_price() {
+        let mut catalog = Catalog::new(
+            CatalogBuilder::new()
+                .with_item(
+                    ItemBuilder::cookie()
+                        .with_sale(
+                            SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                            SalePrice::PercentageOff(0.5),
+                        )
+                        .with_floor_price(1.0),
+                )
+                .with_item(ItemBuilder::brownie().with_floor_price(1.5))
+                .with_item(ItemBuilder::new(5, "Muffin", 1.0).with_floor_price(1.5))
+                .build(),
+        );
+        let friday = &chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let thursday = &chrono::NaiveDate::from_ymd_opt(2021, 9, 30).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 4).unwrap();
+        assert_eq!(cart.total(&catalog, thursday), 5.0);
+        assert_eq!(cart.total(&catalog, friday), 4.0);
+        assert_eq!(cart.floor_clamps(&catalog, friday), vec![("Cookie", 1.5)]);
+        assert!(cart.floor_clamps(&catalog, thursday).is_empty());
+
+        catalog
+            .add_promotion(
+                access::Role::Admin,
+                Promotion::CheapestFree {
+                    qualifying: vec![1, 3],
+                    buy: 2,
+                },
+            )
+            .unwrap();
+        cart.clear();
+        cart.add("Brownie", 2).unwrap();
+        assert_eq!(cart.price(&catalog, thursday), 3.5);
+
+        cart.set_line_price_override(access::Role::Manager, "Brownie", 0.5, "day old")
+            .unwrap();
+        assert_eq!(cart.total(&catalog, thursday), 1.0);
+
+        cart.clear();
+        cart.add("Muffin", 2).unwrap();
+        assert_eq!(cart.total(&catalog, thursday), 2.0);
+        assert!(cart.floor_clamps(&catalog, thursday).is_empty());
+    }
+
+    #[test]
+    fn test_gross_margin() {
+        let mut catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 6).unwrap();
+        cart.add("Brownie", 2).unwrap();
+        catalog.items[2].cost = Some(0.4);
+        assert_eq!(cart.gross_margin(&catalog, dummy_date), None);
+
+        catalog.items[0].cost = Some(0.75);
+        assert_eq!(cart.gross_margin(&catalog, dummy_date), Some(6.1));
+    }
+
+    #[test]
+    fn test_cart_and_catalog_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ShoppingCart>();
+        assert_send_sync::<Catalog>();
+    }
+
+    #[test]
+    fn test_line_price_override() {
+        let catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 6).unwrap();
+        cart.add("Brownie", 1).unwrap();
+        assert_eq!(cart.total(&catalog, dummy_date), 8.0);
+
+        assert_eq!(
+            cart.set_line_price_override(access::Role::Cashier, "Cookie", 0.5, "broken"),
+            Err(CartError::PermissionDenied(access::PermissionDenied {
+                role: access::Role::Cashier,
+                permission: access::Permission::OverridePrice,
+            }))
+        );
+        assert_eq!(
+            cart.set_line_price_override(access::Role::Manager, "Cupcake", 0.5, "broken"),
+            Err(CartError::NotInCart {
+                product: "Cupcake".to_string(),
+            })
+        );
+        assert_eq!(
+            cart.set_line_price_override(access::Role::Manager, "Cookie", -5.0, "broken"),
+            Err(CartError::InvalidPrice { price: -5.0 })
+        );
+        assert!(matches!(
+            cart.set_line_price_override(access::Role::Manager, "Cookie", f64::NAN, "broken"),
+            Err(CartError::InvalidPrice { .. })
+        ));
+        assert!(cart.price_overrides.is_empty());
+        cart.set_line_price_override(access::Role::Manager, "Cookie", 0.5, "broken")
+            .unwrap();
+        assert_eq!(cart.price_overrides["Cookie"].reason, "broken");
+        assert_eq!(cart.total(&catalog, dummy_date), 5.0);
+
+        assert_eq!(
+            cart.checkout_lines(&["Cookie"], &catalog, dummy_date),
+            Ok(3.0)
+        );
+        assert!(cart.price_overrides.is_empty());
+
+        let history: Vec<_> = cart
+            .audit_log
+            .about("Cookie")
+            .map(|entry| (entry.actor, entry.action))
+            .collect();
+        assert_eq!(
+            history,
+            vec![
+                (audit::Actor::Customer, "add"),
+                (
+                    audit::Actor::Staff(access::Role::Manager),
+                    "set_line_price_override"
+                ),
+                (audit::Actor::Customer, "remove_line"),
+            ]
+        );
+        let price_override = &cart.audit_log.entries()[2];
+        assert_eq!(price_override.before, None);
+        assert_eq!(
+            price_override.after,
+            Some(serde_json::json!({ "price": 0.5, "reason": "broken" }))
+        );
+    }
+
+    #[test]
+    fn test_cheapest_free_promotion() {
+        let mut catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        catalog
+            .add_promotion(
+                access::Role::Admin,
+                Promotion::CheapestFree {
+                    qualifying: vec![1, 2, 3],
+                    buy: 3,
+                },
+            )
+            .unwrap();
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Key Lime Cheesecake", 1).unwrap();
+        cart.add("Brownie", 2).unwrap();
+        cart.add("Mini Gingerbread Donut", 3).unwrap();
+        assert_eq!(cart.price(&catalog, dummy_date), 11.5);
+
+        cart.add("Cookie", 1).unwrap();
+        assert_eq!(cart.price(&catalog, dummy_date), 12.75);
+
+        cart.add("Brownie", 3).unwrap();
+        cart.add("Cookie", 2).unwrap();
+        assert_eq!(cart.price(&catalog, dummy_date), 14.75);
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut catalog = Catalog::new(vec![
+            ItemBuilder::brownie().build(),
+            ItemBuilder::cookie()
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                    SalePrice::QuantityForFixedPrice(8, 6.0),
+                )
+                .build(),
+            ItemBuilder::mini_gingerbread_donut()
+                .with_sale(
+                    SaleDate::DayOfWeek(chrono::Weekday::Tue),
+                    SalePrice::TwoForOne,
+                )
+                .build(),
+        ]);
+        catalog
+            .add_promotion(
+                access::Role::Admin,
+                Promotion::CheapestFree {
+                    qualifying: vec![1],
+                    buy: 3,
+                },
+            )
+            .unwrap();
+        catalog
+            .add_promotion(
+                access::Role::Admin,
+                Promotion::CheapestFree {
+                    qualifying: vec![2],
+                    buy: 3,
+                },
+            )
+            .unwrap();
+        let friday = &chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        assert_eq!(
+            cart.summary(&catalog, friday),
+            CartSummary {
+                distinct_products: 0,
+                total_units: 0,
+                applicable_promotions: 0,
+            }
+        );
+
+        cart.add("Brownie", 2).unwrap();
+        cart.add("Cookie", 8).unwrap();
+        cart.add("Mini Gingerbread Donut", 4).unwrap();
+        assert_eq!(
+            cart.summary(&catalog, friday),
+            CartSummary {
+                distinct_products: 3,
+                total_units: 14,
+                applicable_promotions: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_sorted_and_paginated_items() {
+        let catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Key Lime Cheesecake", 1).unwrap();
+        cart.add("Mini Gingerbread Donut", 12).unwrap();
+        cart.add("Brownie", 2).unwrap();
+        cart.add("Cookie", 3).unwrap();
+        cart.add("Cupcake", 1).unwrap();
+        cart.add("Key Lime Cheesecake", 2).unwrap();
+
+        assert_eq!(
+            cart.items_sorted(&catalog.items, SortKey::Name),
+            vec![
+                ("Brownie", 2),
+                ("Cookie", 3),
+                ("Cupcake", 1),
+                ("Key Lime Cheesecake", 2),
+                ("Mini Gingerbread Donut", 12),
+            ]
+        );
+        assert_eq!(
+            cart.items_sorted(&catalog.items, SortKey::Price),
+            vec![
+                ("Mini Gingerbread Donut", 12),
+                ("Cookie", 3),
+                ("Brownie", 2),
+                ("Key Lime Cheesecake", 2),
+                ("Cupcake", 1),
+            ]
+        );
+        assert_eq!(
+            cart.items_page(&catalog.items, SortKey::AddedAt, 1, 2),
+            vec![("Mini Gingerbread Donut", 12), ("Brownie", 2)]
+        );
+        assert_eq!(
+            cart.items_page(&catalog.items, SortKey::AddedAt, 4, 10),
+            vec![("Cupcake", 1)]
+        );
+        assert!(cart
+            .items_page(&catalog.items, SortKey::AddedAt, 10, 10)
+            .is_empty());
+
+        cart.checkout_lines(
+            &["Key Lime Cheesecake"],
+            &catalog,
+            &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap(),
+        )
+        .unwrap();
+        cart.add("Key Lime Cheesecake", 1).unwrap();
+        assert_eq!(
+            cart.items_sorted(&catalog.items, SortKey::AddedAt).last(),
+            Some(&("Key Lime Cheesecake", 1))
+        );
+
+        let selected = cart
+            .select_lines(&["Key Lime Cheesecake", "Brownie", "Cupcake"])
+            .unwrap();
+        assert_eq!(
+            selected.items_sorted(&catalog.items, SortKey::AddedAt),
+            vec![("Brownie", 2), ("Cupcake", 1), ("Key Lime Cheesecake", 1)]
+        );
+        let date = chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+        assert_eq!(
+            selected
+                .breakdown(&catalog, &date)
+                .iter()
+                .map(|line| line.product.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Brownie", "Key Lime Cheesecake"]
+        );
+
+        let mut unordered = ShoppingCart::new();
+        unordered.products.insert("Cookie", 1);
+        unordered.products.insert("Brownie", 1);
+        assert_eq!(
+            unordered.items_sorted(&catalog.items, SortKey::AddedAt),
+            vec![("Brownie", 1), ("Cookie", 1)]
+        );
+    }
 }
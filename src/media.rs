@@ -0,0 +1,32 @@
+//! Checks on the product images referenced by the catalog.
+
+/// Only web images can be shown on the storefront and the POS displays.
+pub fn validate_image_url(url: &str) -> Result<(), String> {
+    match url.split_once("://") {
+        Some(("http" | "https", "")) | None => Err(format!("image URL `{url}` is not a valid URL")),
+        Some(("http" | "https", _)) => Ok(()),
+        Some((scheme, _)) => Err(format!(
+            "image URL `{url}` uses unsupported scheme `{scheme}`"
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_image_url() {
+        assert!(validate_image_url(
+            "https://i.etsystatic.com/29050134/r/il/634971/3087380231/il_794xN.3087380231_n32u.jpg"
+        )
+        .is_ok());
+        assert!(validate_image_url("http://www.mayheminthekitchen.com/wp-content/uploads/2015/05/chocolate-cookie-square.jpg").is_ok());
+        assert_eq!(
+            validate_image_url("file:///tmp/cookie.jpg"),
+            Err("image URL `file:///tmp/cookie.jpg` uses unsupported scheme `file`".to_string())
+        );
+        assert!(validate_image_url("cookie.jpg").is_err());
+        assert!(validate_image_url("https://").is_err());
+    }
+}
@@ -0,0 +1,686 @@
+//! Pricing carts in bulk or across threads, previewing prices under hypothetical changes, and
+//! tracing how a price was chosen.
+
+use crate::{index_items, Catalog, Item, LineBreakdown, Promotion, Sale, ShoppingCart};
+
+/// Above this many ways of splitting the qualifying lines between their own deals and the
+/// promotions, `best_total` estimates a split instead of trying them all.
+const EXHAUSTIVE_LIMIT: usize = 4_096;
+
+/// Totals many carts against the catalog, e.g. to see how much revenue is sitting in open carts
+/// right now. Carts priced on the same date share one catalog index.
+pub fn batch_totals(
+    carts: &[ShoppingCart],
+    catalog: &Catalog,
+    date: &chrono::NaiveDate,
+) -> Vec<f64> {
+    let mut rescheduled_by_date = std::collections::HashMap::new();
+    for cart in carts {
+        let pricing_date = cart.pricing_date(date);
+        rescheduled_by_date
+            .entry(pricing_date)
+            .or_insert_with(|| catalog.rescheduled_items(&pricing_date));
+    }
+    let indexes: std::collections::HashMap<_, _> = rescheduled_by_date
+        .iter()
+        .map(|(pricing_date, rescheduled)| (*pricing_date, catalog.index_on(rescheduled)))
+        .collect();
+    carts
+        .iter()
+        .map(|cart| {
+            cart.total_indexed(
+                &indexes[&cart.pricing_date(date)],
+                &catalog.promotions,
+                date,
+            )
+        })
+        .collect()
+}
+
+/// `ShoppingCart::breakdown` for wholesale carts with tens of thousands of lines, pricing them on
+/// up to `threads` threads that share one catalog index. Lines come back in the order they were
+/// added whatever the thread count, so summing their totals always gives the same result.
+pub fn parallel_breakdown(
+    cart: &ShoppingCart,
+    catalog: &Catalog,
+    date: &chrono::NaiveDate,
+    threads: usize,
+) -> Vec<LineBreakdown> {
+    let date = cart.pricing_date(date);
+    let rescheduled = catalog.rescheduled_items(&date);
+    let index = catalog.index_on(&rescheduled);
+    let lines = cart.items_sorted(&catalog.items, crate::SortKey::AddedAt);
+    let chunk_size = lines.len().div_ceil(threads.max(1)).max(1);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = lines
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let index = &index;
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .filter_map(|(product, quantity)| {
+                            cart.breakdown_line(index, product, *quantity, &date)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().unwrap())
+            .collect()
+    })
+}
+
+/// Prices `cart` with cross-line `promotions`, choosing how many units of each qualifying line
+/// count toward a promotion rather than the line's own bulk deal or sale so the customer pays as
+/// little as possible. A line counts toward the first promotion it qualifies for.
+pub fn best_total(
+    cart: &ShoppingCart,
+    index: &std::collections::HashMap<&str, &Item>,
+    promotions: &[Promotion],
+    date: &chrono::NaiveDate,
+) -> f64 {
+    let date = cart.pricing_date(date);
+    let mut fixed = 0.0;
+    let mut lines = Vec::new();
+    for (product, quantity) in &cart.products {
+        let Some(item) = index.get(product) else {
+            continue;
+        };
+        if let Some(price_override) = cart.price_overrides.get(product) {
+            fixed += *quantity as f64 * price_override.price;
+            continue;
+        }
+        match promotions
+            .iter()
+            .position(|promotion| promotion.pools(item.id))
+        {
+            Some(promotion) => lines.push((*item, *quantity, promotion)),
+            None => fixed += ShoppingCart::line_total(item, *quantity, &date),
+        }
+    }
+    lines.sort_by_key(|(item, _, _)| item.id);
+
+    // The cost when `split[i]` units of `lines[i]` count toward its promotion.
+    let cost = |split: &[usize]| {
+        let mut pools = vec![Vec::new(); promotions.len()];
+        let mut total = fixed;
+        for (&(item, quantity, promotion), &pooled) in lines.iter().zip(split) {
+            total += ShoppingCart::line_total(item, quantity - pooled, &date);
+            let floor_price = item.discount_floor().unwrap_or(0.0);
+            pools[promotion].extend(std::iter::repeat_n((item.price, floor_price), pooled));
+        }
+        for (promotion, pool) in promotions.iter().zip(&mut pools) {
+            total += promotion.pool_total(pool);
+        }
+        total
+    };
+
+    let combinations = lines
+        .iter()
+        .try_fold(1_usize, |combinations, (_, quantity, _)| {
+            combinations.checked_mul(quantity + 1)
+        });
+    if combinations.is_some_and(|combinations| combinations <= EXHAUSTIVE_LIMIT) {
+        let mut split = vec![0; lines.len()];
+        let mut best = cost(&split);
+        loop {
+            // Step to the next split, counting in a mixed radix of each line's quantity.
+            let Some(i) = (0..lines.len()).find(|&i| split[i] < lines[i].1) else {
+                return best;
+            };
+            split[i] += 1;
+            split[..i].fill(0);
+            best = best.min(cost(&split));
+        }
+    }
+
+    let estimated: Vec<usize> = lines
+        .iter()
+        .map(|&(item, quantity, promotion)| {
+            let estimate = |pooled: usize| {
+                ShoppingCart::line_total(item, quantity - pooled, &date)
+                    + pooled as f64 * promotions[promotion].estimated_unit_price(item.price)
+            };
+            (0..=quantity)
+                .min_by(|a, b| estimate(*a).total_cmp(&estimate(*b)))
+                .unwrap()
+        })
+        .collect();
+    let none = vec![0; lines.len()];
+    let all: Vec<usize> = lines.iter().map(|(_, quantity, _)| *quantity).collect();
+    cost(&estimated).min(cost(&none)).min(cost(&all))
+}
+
+/// Hypothetical changes to preview with `simulate`, such as a planned promotion.
+#[derive(Default)]
+pub struct Scenario {
+    /// Unit prices to use instead of the catalog's, by item ID.
+    pub prices: std::collections::HashMap<u32, f64>,
+    /// Sales to use instead of the catalog's, by item ID.
+    pub sales: std::collections::HashMap<u32, Sale>,
+    /// A date to price on instead of the cart's pickup date or `date`.
+    pub date: Option<chrono::NaiveDate>,
+}
+
+/// Prices `cart` as if `scenario` were in effect, without changing the cart or the catalog.
+/// Lines with a price override keep it.
+pub fn simulate(
+    cart: &ShoppingCart,
+    catalog: &Catalog,
+    date: &chrono::NaiveDate,
+    scenario: &Scenario,
+) -> f64 {
+    let rescheduled = catalog.rescheduled_items(&scenario.date.unwrap_or(cart.pricing_date(date)));
+    let mut index = catalog.index_on(&rescheduled);
+    let changed: Vec<Item> = index
+        .values()
+        .filter(|item| {
+            scenario.prices.contains_key(&item.id) || scenario.sales.contains_key(&item.id)
+        })
+        .map(|item| Item {
+            price: scenario.prices.get(&item.id).copied().unwrap_or(item.price),
+            sale: scenario.sales.get(&item.id).or(item.sale.as_ref()).cloned(),
+            ..(*item).clone()
+        })
+        .collect();
+    index.extend(index_items(&changed));
+
+    let mut preview = ShoppingCart::new();
+    preview.products = cart.products.clone();
+    preview.pickup = cart.pickup;
+    preview.price_overrides = cart.price_overrides.clone();
+    if let Some(date) = scenario.date {
+        preview.pickup = None;
+        return preview.total_indexed(&index, &catalog.promotions, &date);
+    }
+    preview.total_indexed(&index, &catalog.promotions, date)
+}
+
+/// Why a pricing rule didn't set a line's price.
+#[derive(Debug, PartialEq)]
+pub enum Rejection {
+    /// A manager's price override replaced every other rule.
+    Overridden,
+    /// The sale isn't on for the pricing date.
+    NotOnSale,
+    /// The item has a sale, which replaces its bulk pricing whether or not the sale is on.
+    ReplacedBySale,
+    BelowBulkAmount,
+    /// A sale or bulk deal applied instead.
+    DealApplied,
+    /// The rule would have priced the line below the item's floor price.
+    BelowFloorPrice,
+    /// The discounted price was already at or above the floor price.
+    AboveFloorPrice,
+}
+
+/// A pricing rule considered for a line, what it would have charged, and why it lost if it did.
+#[derive(Debug, PartialEq)]
+pub struct Candidate {
+    pub rule: &'static str,
+    pub total: f64,
+    pub rejection: Option<Rejection>,
+}
+
+/// Every rule considered for one line, from `trace`.
+#[derive(Debug, PartialEq)]
+pub struct LineTrace {
+    pub product: String,
+    pub candidates: Vec<Candidate>,
+    /// The cross-line promotion, by index into the catalog's, that some of the line's units may
+    /// count toward instead.
+    pub promotion: Option<usize>,
+}
+
+/// Records every rule considered when pricing each line of `cart`, which one set the price and
+/// why the others didn't, for debugging promotion setups. Lines are in the order they were added.
+/// This is separate from `best_total` so ordinary pricing doesn't pay for it.
+pub fn trace(cart: &ShoppingCart, catalog: &Catalog, date: &chrono::NaiveDate) -> Vec<LineTrace> {
+    let date = cart.pricing_date(date);
+    let rescheduled = catalog.rescheduled_items(&date);
+    let index = catalog.index_on(&rescheduled);
+    let mut traces = Vec::new();
+    for (product, quantity) in cart.items_sorted(&catalog.items, crate::SortKey::AddedAt) {
+        let Some(item) = index.get(product) else {
+            continue;
+        };
+        let price_override = cart.price_overrides.get(product);
+        let sale_on = item
+            .sale
+            .as_ref()
+            .is_some_and(|sale| sale.date.matches(&date));
+        let bulk_applies = item.sale.is_none()
+            && item
+                .bulk_pricing
+                .as_ref()
+                .is_some_and(|bulk_pricing| quantity >= bulk_pricing.amount as usize);
+        let floor_total = ShoppingCart::line_total(item, quantity, &date);
+        let floored = floor_total > ShoppingCart::discounted_line_total(item, quantity, &date);
+        let chosen = if price_override.is_some() {
+            "price override"
+        } else if floored {
+            "floor price"
+        } else if sale_on {
+            "sale"
+        } else if bulk_applies {
+            "bulk pricing"
+        } else {
+            "regular price"
+        };
+
+        let mut candidates = Vec::new();
+        // A rule that could apply but lost was outranked by the chosen one. One that couldn't
+        // apply says why not.
+        let mut consider = |rule, total: f64, applies, reason| {
+            let rejection = if rule == chosen {
+                None
+            } else if applies {
+                Some(match chosen {
+                    "price override" => Rejection::Overridden,
+                    "floor price" if total < floor_total => Rejection::BelowFloorPrice,
+                    _ => Rejection::DealApplied,
+                })
+            } else {
+                Some(reason)
+            };
+            candidates.push(Candidate {
+                rule,
+                total,
+                rejection,
+            });
+        };
+        if let Some(price_override) = price_override {
+            let total = price_override.price * quantity as f64;
+            consider("price override", total, true, Rejection::Overridden);
+        }
+        if let Some(sale) = &item.sale {
+            let total = ShoppingCart::apply_sale_price(&sale.sale_price, quantity, item.price);
+            consider("sale", total, sale_on, Rejection::NotOnSale);
+        }
+        if let Some(bulk_pricing) = &item.bulk_pricing {
+            let total = ShoppingCart::apply_bulk_pricing(bulk_pricing, quantity, item.price);
+            let reason = match item.sale {
+                Some(_) => Rejection::ReplacedBySale,
+                None => Rejection::BelowBulkAmount,
+            };
+            consider("bulk pricing", total, bulk_applies, reason);
+        }
+        let total = item.price * quantity as f64;
+        consider("regular price", total, true, Rejection::DealApplied);
+        if let Some(floor_price) = item.discount_floor() {
+            let total = floor_price * quantity as f64;
+            consider("floor price", total, floored, Rejection::AboveFloorPrice);
+        }
+
+        traces.push(LineTrace {
+            product: product.to_string(),
+            candidates,
+            promotion: match price_override {
+                Some(_) => None,
+                None => catalog
+                    .promotions
+                    .iter()
+                    .position(|promotion| promotion.pools(item.id)),
+            },
+        });
+    }
+    traces
+}
+
+/// Splits an order-level `discount` across lines in proportion to `line_totals`, so the shares
+/// add up to exactly the discount in cents. Each line gets the whole cents of its share, and the
+/// cents left over go to the lines with the largest fractions, ties going to the earlier line.
+/// The discount is capped at the order total, so an order with nothing to pay gets no discount.
+pub fn allocate_discount(discount: f64, line_totals: &[f64]) -> Vec<f64> {
+    let cents: Vec<i128> = line_totals
+        .iter()
+        .map(|total| (total.max(0.0) * 100.0).round() as i128)
+        .collect();
+    let order_total: i128 = cents.iter().sum();
+    if order_total == 0 {
+        return vec![0.0; cents.len()];
+    }
+    let discount = ((discount.max(0.0) * 100.0).round() as i128).min(order_total);
+
+    let mut shares: Vec<i128> = cents
+        .iter()
+        .map(|line| discount * line / order_total)
+        .collect();
+    let mut by_remainder: Vec<usize> = (0..cents.len()).collect();
+    by_remainder.sort_by_key(|&line| std::cmp::Reverse(discount * cents[line] % order_total));
+    let left_over = discount - shares.iter().sum::<i128>();
+    for &line in by_remainder.iter().take(left_over as usize) {
+        shares[line] += 1;
+    }
+    shares
+        .into_iter()
+        .map(|share| share as f64 / 100.0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{CatalogBuilder, ItemBuilder};
+    use crate::{SaleDate, SalePrice};
+
+    #[test]
+    fn test_best_total_doesnt_double_count_units() {
+        let mut catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        catalog
+            .add_promotion(
+                crate::access::Role::Admin,
+                Promotion::CheapestFree {
+                    qualifying: vec![1, 3],
+                    buy: 3,
+                },
+            )
+            .unwrap();
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 6).unwrap();
+        assert_eq!(cart.price(&catalog, dummy_date), 5.0);
+
+        cart.add("Cookie", 7).unwrap();
+        cart.add("Brownie", 2).unwrap();
+        assert_eq!(cart.price(&catalog, dummy_date), 9.0);
+
+        cart.add("Cookie", 100).unwrap();
+        cart.add("Brownie", 100).unwrap();
+        assert_eq!(cart.price(&catalog, dummy_date), 217.75);
+    }
+
+    #[test]
+    fn test_totals_include_promotions() {
+        let mut catalog = Catalog::new(
+            CatalogBuilder::new()
+                .with_item(ItemBuilder::cookie().with_cost(0.5))
+                .build(),
+        );
+        catalog
+            .add_promotion(
+                crate::access::Role::Admin,
+                Promotion::CheapestFree {
+                    qualifying: vec![3],
+                    buy: 3,
+                },
+            )
+            .unwrap();
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 6).unwrap();
+        assert_eq!(cart.total(&catalog, dummy_date), 5.0);
+        assert_eq!(cart.gross_margin(&catalog, dummy_date), Some(2.0));
+        assert_eq!(
+            batch_totals(
+                &[cart.select_lines(&["Cookie"]).unwrap()],
+                &catalog,
+                dummy_date
+            ),
+            vec![5.0]
+        );
+        assert_eq!(
+            simulate(&cart, &catalog, dummy_date, &Scenario::default()),
+            5.0
+        );
+        assert_eq!(cart.price(&catalog, dummy_date), 5.0);
+
+        catalog
+            .add_promotion(
+                crate::access::Role::Admin,
+                Promotion::GiftWithPurchase {
+                    qualifying: vec![3],
+                    min_units: 6,
+                    gift: 3,
+                },
+            )
+            .unwrap();
+        assert_eq!(cart.total(&catalog, dummy_date), 5.0);
+        assert_eq!(cart.gross_margin(&catalog, dummy_date), Some(1.5));
+    }
+
+    #[test]
+    fn test_parallel_breakdown() {
+        let names: Vec<String> = (1..=1_000).map(|id| format!("Wholesale {id}")).collect();
+        let mut builder = CatalogBuilder::new();
+        for (id, name) in (1..).zip(&names) {
+            let mut item = ItemBuilder::new(id, name, f64::from(id % 7) + 0.25);
+            if id % 3 == 0 {
+                item = item.with_bulk(12, 10.0);
+            }
+            builder = builder.with_item(item);
+        }
+        let catalog = Catalog::new(builder.build());
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.set_limits(crate::CartLimits {
+            max_lines: names.len(),
+            ..crate::CartLimits::default()
+        });
+        for (quantity, name) in (1..).zip(names.iter().rev()) {
+            cart.add(name, quantity % 25).unwrap();
+        }
+        cart.set_line_price_override(crate::access::Role::Manager, "Wholesale 9", 0.1, "damaged")
+            .unwrap();
+
+        let breakdown = cart.breakdown(&catalog, dummy_date);
+        assert_eq!(breakdown.len(), 1_000);
+        assert_eq!(breakdown[0].product, "Wholesale 1000");
+        for threads in [0, 1, 3, 8, 2_000] {
+            assert_eq!(
+                parallel_breakdown(&cart, &catalog, dummy_date, threads),
+                breakdown
+            );
+        }
+        assert!(parallel_breakdown(&ShoppingCart::new(), &catalog, dummy_date, 4).is_empty());
+    }
+
+    #[test]
+    fn test_batch_totals() {
+        let catalog = Catalog::new(vec![ItemBuilder::brownie().with_bulk(4, 7.0).build()]);
+        let dummy_date = &chrono::NaiveDate::from_ymd_opt(1, 1, 1).unwrap();
+
+        let mut one = ShoppingCart::new();
+        one.add("Brownie", 1).unwrap();
+        let mut four = ShoppingCart::new();
+        four.add("Brownie", 4).unwrap();
+
+        assert_eq!(
+            batch_totals(&[one, four, ShoppingCart::new()], &catalog, dummy_date),
+            vec![2.0, 7.0, 0.0]
+        );
+    }
+
+    #[test]
+    fn test_simulate() {
+        let catalog = Catalog::new(CatalogBuilder::bakery_demo().build());
+        let monday = &chrono::NaiveDate::from_ymd_opt(2021, 10, 4).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 2).unwrap();
+        cart.add("Key Lime Cheesecake", 1).unwrap();
+        assert_eq!(
+            simulate(&cart, &catalog, monday, &Scenario::default()),
+            10.5
+        );
+
+        let mut scenario = Scenario::default();
+        scenario.prices.insert(2, 10.0);
+        scenario.sales.insert(
+            3,
+            Sale {
+                date: SaleDate::DayOfWeek(chrono::Weekday::Tue),
+                sale_price: SalePrice::TwoForOne,
+            },
+        );
+        assert_eq!(simulate(&cart, &catalog, monday, &scenario), 12.5);
+
+        scenario.date = Some(chrono::NaiveDate::from_ymd_opt(2021, 10, 5).unwrap());
+        assert_eq!(simulate(&cart, &catalog, monday, &scenario), 11.25);
+
+        assert_eq!(cart.total(&catalog, monday), 10.5);
+        assert_eq!(catalog.items[1].price, 8.0);
+
+        cart.add("Brownie", 1).unwrap();
+        cart.set_line_price_override(crate::access::Role::Manager, "Brownie", 0.5, "day old")
+            .unwrap();
+        assert_eq!(cart.total(&catalog, monday), 11.0);
+        assert_eq!(
+            simulate(&cart, &catalog, monday, &Scenario::default()),
+            11.0
+        );
+        scenario.prices.insert(1, 3.0);
+        assert_eq!(simulate(&cart, &catalog, monday, &scenario), 11.75);
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut catalog = Catalog::new(
+            CatalogBuilder::bakery_demo()
+                .with_item(
+                    ItemBuilder::new(5, "Eclair", 3.0)
+                        .with_sale(
+                            SaleDate::DayOfWeek(chrono::Weekday::Fri),
+                            SalePrice::PercentageOff(0.5),
+                        )
+                        .with_bulk(4, 10.0)
+                        .with_floor_price(2.0),
+                )
+                .build(),
+        );
+        catalog
+            .add_promotion(
+                crate::access::Role::Admin,
+                Promotion::CheapestFree {
+                    qualifying: vec![3],
+                    buy: 3,
+                },
+            )
+            .unwrap();
+        let friday = &chrono::NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let thursday = &chrono::NaiveDate::from_ymd_opt(2021, 9, 30).unwrap();
+
+        let mut cart = ShoppingCart::new();
+        cart.add("Cookie", 4).unwrap();
+        cart.add("Eclair", 4).unwrap();
+
+        let traces = trace(&cart, &catalog, thursday);
+        assert_eq!(
+            traces[0],
+            LineTrace {
+                product: "Cookie".to_string(),
+                candidates: vec![
+                    Candidate {
+                        rule: "bulk pricing",
+                        total: 5.0,
+                        rejection: Some(Rejection::BelowBulkAmount),
+                    },
+                    Candidate {
+                        rule: "regular price",
+                        total: 5.0,
+                        rejection: None,
+                    },
+                ],
+                promotion: Some(0),
+            }
+        );
+        let eclair = |traces: Vec<LineTrace>| -> Vec<(&'static str, Option<Rejection>)> {
+            traces
+                .into_iter()
+                .nth(1)
+                .unwrap()
+                .candidates
+                .into_iter()
+                .map(|candidate| (candidate.rule, candidate.rejection))
+                .collect()
+        };
+        assert_eq!(
+            eclair(traces),
+            vec![
+                ("sale", Some(Rejection::NotOnSale)),
+                ("bulk pricing", Some(Rejection::ReplacedBySale)),
+                ("regular price", None),
+                ("floor price", Some(Rejection::AboveFloorPrice)),
+            ]
+        );
+        assert_eq!(
+            eclair(trace(&cart, &catalog, friday)),
+            vec![
+                ("sale", Some(Rejection::BelowFloorPrice)),
+                ("bulk pricing", Some(Rejection::ReplacedBySale)),
+                ("regular price", Some(Rejection::DealApplied)),
+                ("floor price", None),
+            ]
+        );
+        for date in [friday, thursday] {
+            let bulk = &trace(&cart, &catalog, date)[1].candidates[1];
+            assert_eq!((bulk.rule, bulk.total), ("bulk pricing", 10.0));
+        }
+
+        cart.set_line_price_override(crate::access::Role::Manager, "Eclair", 1.0, "seconds")
+            .unwrap();
+        assert_eq!(
+            eclair(trace(&cart, &catalog, friday))[..2],
+            [
+                ("price override", None),
+                ("sale", Some(Rejection::Overridden)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_allocate_discount() {
+        let cents = |shares: Vec<f64>| -> Vec<i64> {
+            shares
+                .into_iter()
+                .map(|share| (share * 100.0).round() as i64)
+                .collect()
+        };
+
+        // 10% off 10.00 is 1.00, and the 0.034 share is the largest fraction left over.
+        assert_eq!(
+            cents(allocate_discount(1.0, &[3.33, 3.33, 3.34])),
+            vec![33, 33, 34]
+        );
+        // A cent across equal lines goes to the first.
+        assert_eq!(
+            cents(allocate_discount(0.01, &[1.0, 1.0, 1.0])),
+            vec![1, 0, 0]
+        );
+        assert_eq!(
+            cents(allocate_discount(0.02, &[1.0, 1.0, 1.0])),
+            vec![1, 1, 0]
+        );
+        // Lines too small to earn a whole cent still pick up left over cents.
+        assert_eq!(
+            cents(allocate_discount(0.05, &[0.01, 0.01, 99.98])),
+            vec![0, 0, 5]
+        );
+        assert_eq!(
+            cents(allocate_discount(0.03, &[0.01, 0.01, 0.01])),
+            vec![1, 1, 1]
+        );
+        // Never more than the order is worth.
+        assert_eq!(cents(allocate_discount(50.0, &[2.5, 7.5])), vec![250, 750]);
+        // Free lines get nothing, and free orders have nothing to discount.
+        assert_eq!(cents(allocate_discount(1.0, &[0.0, 4.0])), vec![0, 100]);
+        assert_eq!(cents(allocate_discount(1.0, &[0.0, 0.0])), vec![0, 0]);
+        assert!(allocate_discount(1.0, &[]).is_empty());
+
+        let line_totals: Vec<f64> = (1..=97).map(|line| line as f64 * 0.37).collect();
+        let order_total: f64 = line_totals.iter().sum();
+        let discount = (order_total * 0.1 * 100.0).round() / 100.0;
+        let shares = cents(allocate_discount(discount, &line_totals));
+        assert_eq!(
+            shares.iter().sum::<i64>(),
+            (discount * 100.0).round() as i64
+        );
+        assert!(shares.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+}
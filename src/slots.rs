@@ -0,0 +1,145 @@
+//! Pickup and delivery windows, each taking a limited number of orders.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::BTreeMap;
+
+/// A window orders can be picked up or delivered in, from `start` up to but not including `end`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Slot {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+struct Booking {
+    end: NaiveDateTime,
+    capacity: u32,
+    booked: u32,
+}
+
+#[derive(Default)]
+pub struct PickupSlots {
+    slots: BTreeMap<NaiveDateTime, Booking>,
+}
+
+impl PickupSlots {
+    /// Opens a window taking up to `capacity` orders, replacing any window with the same start.
+    pub fn add_slot(&mut self, start: NaiveDateTime, end: NaiveDateTime, capacity: u32) {
+        self.slots.insert(
+            start,
+            Booking {
+                end,
+                capacity,
+                booked: 0,
+            },
+        );
+    }
+
+    /// The window `time` falls in, if any.
+    pub fn slot_for(&self, time: NaiveDateTime) -> Option<Slot> {
+        self.booking_for(time).map(|(start, booking)| Slot {
+            start,
+            end: booking.end,
+        })
+    }
+
+    /// How many more orders the window `time` falls in can take, or `None` if there isn't one.
+    pub fn remaining(&self, time: NaiveDateTime) -> Option<u32> {
+        let (_, booking) = self.booking_for(time)?;
+        Some(booking.capacity.saturating_sub(booking.booked))
+    }
+
+    /// Every window starting on `date` with how many more orders it can take, for the slot picker.
+    pub fn available(&self, date: NaiveDate) -> Vec<(Slot, u32)> {
+        let from = date.and_hms_opt(0, 0, 0).unwrap();
+        self.slots
+            .range(from..)
+            .take_while(|(start, _)| start.date() == date)
+            .map(|(start, booking)| {
+                let slot = Slot {
+                    start: *start,
+                    end: booking.end,
+                };
+                (slot, booking.capacity.saturating_sub(booking.booked))
+            })
+            .collect()
+    }
+
+    /// Books an order into the window `time` falls in, or returns `None` if there's no window
+    /// or it's full.
+    pub fn reserve(&mut self, time: NaiveDateTime) -> Option<Slot> {
+        let (start, _) = self.booking_for(time)?;
+        let booking = self.slots.get_mut(&start)?;
+        if booking.booked >= booking.capacity {
+            return None;
+        }
+        booking.booked += 1;
+        Some(Slot {
+            start,
+            end: booking.end,
+        })
+    }
+
+    /// Gives back an order's place in the window `time` falls in, e.g. when the order is cancelled.
+    pub fn release(&mut self, time: NaiveDateTime) {
+        if let Some((start, _)) = self.booking_for(time) {
+            if let Some(booking) = self.slots.get_mut(&start) {
+                booking.booked = booking.booked.saturating_sub(1);
+            }
+        }
+    }
+
+    fn booking_for(&self, time: NaiveDateTime) -> Option<(NaiveDateTime, &Booking)> {
+        let (start, booking) = self.slots.range(..=time).next_back()?;
+        (time < booking.end).then_some((*start, booking))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pickup_slots() {
+        let friday = NaiveDate::from_ymd_opt(2021, 10, 1).unwrap();
+        let at = |hour, minute| friday.and_hms_opt(hour, minute, 0).unwrap();
+        let mut slots = PickupSlots::default();
+        slots.add_slot(at(9, 0), at(10, 0), 2);
+        slots.add_slot(at(10, 0), at(11, 0), 1);
+        slots.add_slot(
+            at(9, 0) + chrono::Days::new(1),
+            at(10, 0) + chrono::Days::new(1),
+            5,
+        );
+
+        assert_eq!(slots.remaining(at(8, 59)), None);
+        assert_eq!(slots.remaining(at(11, 0)), None);
+        assert_eq!(
+            slots.slot_for(at(9, 59)),
+            Some(Slot {
+                start: at(9, 0),
+                end: at(10, 0),
+            })
+        );
+
+        assert_eq!(
+            slots.reserve(at(10, 30)).map(|slot| slot.start),
+            Some(at(10, 0))
+        );
+        assert_eq!(slots.reserve(at(10, 15)), None);
+        assert_eq!(slots.reserve(at(12, 0)), None);
+        assert!(slots.reserve(at(9, 15)).is_some());
+        assert_eq!(
+            slots
+                .available(friday)
+                .into_iter()
+                .map(|(slot, remaining)| (slot.start, remaining))
+                .collect::<Vec<_>>(),
+            vec![(at(9, 0), 1), (at(10, 0), 0)]
+        );
+
+        slots.release(at(10, 45));
+        assert_eq!(slots.remaining(at(10, 0)), Some(1));
+        slots.release(at(10, 45));
+        assert_eq!(slots.remaining(at(10, 0)), Some(1));
+    }
+}